@@ -0,0 +1,9 @@
+%------------------------------------------------------------------------------
+% Clause form: the conjecture has been negated, so the clauses are
+% unsatisfiable exactly when it follows from the axioms.
+%------------------------------------------------------------------------------
+cnf(parent_is_ancestor, axiom, ~ parent(X, Y) | ancestor(X, Y)).
+cnf(transitive, axiom, ( ~ ancestor(X, Y) | ~ ancestor(Y, Z) | ancestor(X, Z) )).
+cnf(abe, axiom, parent(abe, homer)).
+cnf(homer, axiom, parent(homer, bart)).
+cnf(goal, negated_conjecture, ~ ancestor(abe, homer)).
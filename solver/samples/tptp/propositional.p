@@ -0,0 +1,12 @@
+%------------------------------------------------------------------------------
+% Propositional reasoning with every binary connective of TPTP.
+%------------------------------------------------------------------------------
+fof(rule, axiom, (rain => wet)).
+fof(fact, axiom, rain).
+fof(exclusive, hypothesis, (wet <~> dry)).
+fof(converse, axiom, (cold <= (wet & ~ dry))).
+fof(not_both, axiom, (hot ~& cold)).
+fof(neither, axiom, ~ (snow ~| hail)).
+fof(goal, conjecture, ((wet & ~ dry) & ~ hot)).
+/* Nothing below is needed for the proof. */
+fof(unused, axiom, ($true <=> ~ $false), [source('example')]).
@@ -0,0 +1,9 @@
+%------------------------------------------------------------------------------
+% All men are mortal, Socrates is a man, so Socrates is mortal; plus some
+% equality reasoning with functions.
+%------------------------------------------------------------------------------
+fof(all_men_mortal, axiom, ! [X] : (man(X) => mortal(X))).
+fof(socrates, axiom, man(socrates)).
+fof(teacher, axiom, teacher(plato) = socrates).
+fof('Every teacher', axiom, ! [X, Y] : (teacher(X) = Y => X != Y)).
+fof(mortal_teacher, conjecture, ? [X] : mortal(teacher(X))).
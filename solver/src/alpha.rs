@@ -0,0 +1,120 @@
+use crate::ast::{Connective, Term};
+use crate::visit::{rebuild_connective, rebuild_term, Fold};
+
+/// Renames every bound variable after the number of quantifiers around its binder,
+/// its de Bruijn level.
+#[derive(Default)]
+pub(crate) struct Canonical {
+    scope: Vec<String>,
+}
+
+impl Canonical {
+    /// The canonical name of the variable `x`, if a quantifier in scope binds it.
+    fn lookup(&self, x: &str) -> Option<String> {
+        let level = self.scope.iter().rposition(|bound| bound == x)?;
+        Some(level_name(level))
+    }
+
+    /// Whether no quantifier is in scope.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.scope.is_empty()
+    }
+
+    /// Enters a quantifier binding `x`, giving the canonical name for it.
+    pub(crate) fn bind(&mut self, x: &str) -> String {
+        let name = level_name(self.scope.len());
+        self.scope.push(x.to_string());
+        name
+    }
+
+    /// Leaves the innermost quantifier.
+    pub(crate) fn unbind(&mut self) {
+        self.scope.pop();
+    }
+
+    /// The canonical name of `x`, which is `x` itself if it is free.
+    pub(crate) fn rename(&self, x: &str) -> String {
+        self.lookup(x).unwrap_or_else(|| x.to_string())
+    }
+}
+
+/// The name given to the variable bound at `level`, which the parser never
+/// produces, so it can't clash with a free name.
+fn level_name(level: usize) -> String {
+    format!("#{}", level)
+}
+
+impl Fold for Canonical {
+    fn fold_connective(&mut self, con: &Connective) -> Connective {
+        match con {
+            Connective::Var(x) => Connective::Var(self.rename(x)),
+            Connective::ForAll(x, sort, inner) | Connective::Exists(x, sort, inner) => {
+                let name = self.bind(x);
+                let inner = box self.fold_connective(inner);
+                self.unbind();
+                match con {
+                    Connective::ForAll(_, _, _) => Connective::ForAll(name, sort.clone(), inner),
+                    _ => Connective::Exists(name, sort.clone(), inner),
+                }
+            }
+            _ => rebuild_connective(self, con),
+        }
+    }
+
+    fn fold_term(&mut self, term: &Term) -> Term {
+        match term {
+            Term::Var(x) => Term::Var(self.rename(x)),
+            _ => rebuild_term(self, term),
+        }
+    }
+}
+
+impl Connective {
+    /// The formula with its bound variables renamed by their de Bruijn level, so
+    /// that formulas which only differ in the names of bound variables become
+    /// equal. `∀x ∃y R(x, y)` and `∀y ∃x R(y, x)` both become `∀#0 ∃#1 R(#0, #1)`.
+    pub fn alpha_canonical(&self) -> Connective {
+        Canonical::default().fold_connective(self)
+    }
+
+    /// Whether the formulas are the same up to the names of bound variables.
+    pub fn alpha_eq(&self, other: &Connective) -> bool {
+        self.alpha_canonical() == other.alpha_canonical()
+    }
+}
+
+#[cfg(test)]
+use crate::parse::parse;
+
+#[test]
+fn alpha_equivalence() {
+    let eq = |a, b| parse(a).unwrap().alpha_eq(&parse(b).unwrap());
+    assert!(eq("∀x P(x)", "∀y P(y)"));
+    assert!(eq("∀x ∃y R(x, y)", "∀y ∃x R(y, x)"));
+    assert!(eq(
+        "∀x (P(x) ∧ ∃x Q(x)) ∨ ∃z P(z)",
+        "∀y (P(y) ∧ ∃y Q(y)) ∨ ∃x P(x)"
+    ));
+    assert!(eq("∀x:Pet x ≈ f(x)", "∀y:Pet y ≈ f(y)"));
+    assert!(eq("p ∧ q", "p ∧ q"));
+
+    assert!(!eq("∀x ∃y R(x, y)", "∀x ∃y R(y, x)"));
+    assert!(!eq("∀x ∀x P(x)", "∀x ∀y P(x)"));
+    assert!(!eq("∀x P(x, a)", "∀x P(x, b)"));
+    assert!(!eq("∀x:Pet P(x)", "∀x:Person P(x)"));
+    assert!(!eq("∀x P(x)", "∃x P(x)"));
+}
+
+#[test]
+fn canonical_names_are_levels() {
+    let con = parse("∀x ∃y R(x, y) ∧ ∃z P(z)").unwrap().alpha_canonical();
+    assert_eq!(con.pretty(), "∀#0 ∃#1 R(#0, #1) ∧ ∃#0 P(#0)");
+    assert_eq!(con.alpha_canonical(), con);
+
+    // Free variables keep their names.
+    let body = match parse("∀x ∃y R(x, y)").unwrap() {
+        Connective::ForAll(_, _, body) => *body,
+        _ => unreachable!(),
+    };
+    assert_eq!(body.alpha_canonical().pretty(), "∃#0 R(x, #0)");
+}
@@ -0,0 +1,469 @@
+//! Hash-consed formulas, for code that builds and compares many of them.
+//!
+//! An [`Arena`] stores every distinct subformula once and hands out a
+//! [`FormulaId`] for it. Interning the same formula twice gives the same id, so
+//! comparing or hashing formulas is comparing or hashing ids, and formulas built
+//! from one another share their unchanged parts instead of copying them:
+//!
+//! ```
+//! # use solver::parse;
+//! # use solver::arena::Arena;
+//! let mut arena = Arena::new();
+//! let a = arena.intern(&parse("(p ∧ q) ∨ (p ∧ q)").unwrap());
+//! let b = arena.intern(&parse("p ∧ q").unwrap());
+//! assert_eq!(arena.len(), 4);
+//! assert_eq!(arena[a].operands(), vec![b, b]);
+//! assert_eq!(arena.get(a), parse("(p ∧ q) ∨ (p ∧ q)").unwrap());
+//! ```
+//!
+//! Ids are only meaningful in the arena that made them.
+
+use crate::alpha::Canonical;
+use crate::ast::{Connective, Term};
+use crate::visit::Fold;
+use indexmap::{IndexMap, IndexSet};
+use std::collections::HashMap;
+use std::ops::Index;
+
+/// A formula interned in an [`Arena`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct FormulaId(usize);
+
+/// One level of a formula, with its operands given by id. Mirrors [`Connective`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Formula {
+    Var(String),
+    Top,
+    Bottom,
+    Error,
+    Predicate(String, Vec<Term>),
+    Eq(Term, Term),
+    Not(FormulaId),
+    And(FormulaId, FormulaId),
+    Or(FormulaId, FormulaId),
+    Implicate(FormulaId, FormulaId),
+    Biimplicate(FormulaId, FormulaId),
+    Xor(FormulaId, FormulaId),
+    Nand(FormulaId, FormulaId),
+    Nor(FormulaId, FormulaId),
+    ReverseImplicate(FormulaId, FormulaId),
+    ForAll(String, Option<String>, FormulaId),
+    Exists(String, Option<String>, FormulaId),
+    Box(FormulaId),
+    Diamond(FormulaId),
+    Next(FormulaId),
+    Eventually(FormulaId),
+    Always(FormulaId),
+    Until(FormulaId, FormulaId),
+}
+
+impl Formula {
+    /// The immediate subformulas, left to right. Atoms have none.
+    pub fn operands(&self) -> Vec<FormulaId> {
+        match self {
+            Formula::Var(_)
+            | Formula::Top
+            | Formula::Bottom
+            | Formula::Error
+            | Formula::Predicate(_, _)
+            | Formula::Eq(_, _) => vec![],
+            Formula::Not(x)
+            | Formula::Box(x)
+            | Formula::Diamond(x)
+            | Formula::Next(x)
+            | Formula::Eventually(x)
+            | Formula::Always(x)
+            | Formula::ForAll(_, _, x)
+            | Formula::Exists(_, _, x) => vec![*x],
+            Formula::And(a, b)
+            | Formula::Or(a, b)
+            | Formula::Implicate(a, b)
+            | Formula::Biimplicate(a, b)
+            | Formula::Xor(a, b)
+            | Formula::Nand(a, b)
+            | Formula::Nor(a, b)
+            | Formula::ReverseImplicate(a, b)
+            | Formula::Until(a, b) => vec![*a, *b],
+        }
+    }
+
+    /// The same formula with each operand replaced by `f` of it.
+    pub fn map_operands(&self, mut f: impl FnMut(FormulaId) -> FormulaId) -> Formula {
+        match self {
+            Formula::Var(_)
+            | Formula::Top
+            | Formula::Bottom
+            | Formula::Error
+            | Formula::Predicate(_, _)
+            | Formula::Eq(_, _) => self.clone(),
+            Formula::Not(x) => Formula::Not(f(*x)),
+            Formula::Box(x) => Formula::Box(f(*x)),
+            Formula::Diamond(x) => Formula::Diamond(f(*x)),
+            Formula::Next(x) => Formula::Next(f(*x)),
+            Formula::Eventually(x) => Formula::Eventually(f(*x)),
+            Formula::Always(x) => Formula::Always(f(*x)),
+            Formula::Until(a, b) => Formula::Until(f(*a), f(*b)),
+            Formula::And(a, b) => Formula::And(f(*a), f(*b)),
+            Formula::Or(a, b) => Formula::Or(f(*a), f(*b)),
+            Formula::Implicate(a, b) => Formula::Implicate(f(*a), f(*b)),
+            Formula::Biimplicate(a, b) => Formula::Biimplicate(f(*a), f(*b)),
+            Formula::Xor(a, b) => Formula::Xor(f(*a), f(*b)),
+            Formula::Nand(a, b) => Formula::Nand(f(*a), f(*b)),
+            Formula::Nor(a, b) => Formula::Nor(f(*a), f(*b)),
+            Formula::ReverseImplicate(a, b) => Formula::ReverseImplicate(f(*a), f(*b)),
+            Formula::ForAll(x, sort, inner) => Formula::ForAll(x.clone(), sort.clone(), f(*inner)),
+            Formula::Exists(x, sort, inner) => Formula::Exists(x.clone(), sort.clone(), f(*inner)),
+        }
+    }
+}
+
+/// Owns interned formulas, each stored once however often it occurs.
+#[derive(Debug, Clone, Default)]
+pub struct Arena {
+    formulas: Vec<Formula>,
+    ids: HashMap<Formula, FormulaId>,
+    /// The alpha-canonical forms found so far, see [`Arena::canonical`].
+    canonical: HashMap<FormulaId, FormulaId>,
+}
+
+impl Arena {
+    pub fn new() -> Arena {
+        Arena::default()
+    }
+
+    /// The number of distinct formulas interned.
+    pub fn len(&self) -> usize {
+        self.formulas.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.formulas.is_empty()
+    }
+
+    /// The id of `formula`, storing it if it is new. Its operands must come from
+    /// this arena.
+    pub fn add(&mut self, formula: Formula) -> FormulaId {
+        if let Some(id) = self.ids.get(&formula) {
+            return *id;
+        }
+        let id = FormulaId(self.formulas.len());
+        self.formulas.push(formula.clone());
+        self.ids.insert(formula, id);
+        id
+    }
+
+    /// The id of `con`, storing it and any of its subformulas that are new.
+    pub fn intern(&mut self, con: &Connective) -> FormulaId {
+        let formula = match con {
+            Connective::Var(x) => Formula::Var(x.clone()),
+            Connective::Top => Formula::Top,
+            Connective::Bottom => Formula::Bottom,
+            Connective::Error => Formula::Error,
+            Connective::Predicate(p, args) => Formula::Predicate(p.clone(), args.clone()),
+            Connective::Eq(a, b) => Formula::Eq(a.clone(), b.clone()),
+            Connective::Not(x) => Formula::Not(self.intern(x)),
+            Connective::And(a, b) => Formula::And(self.intern(a), self.intern(b)),
+            Connective::Or(a, b) => Formula::Or(self.intern(a), self.intern(b)),
+            Connective::Implicate(a, b) => Formula::Implicate(self.intern(a), self.intern(b)),
+            Connective::Biimplicate(a, b) => Formula::Biimplicate(self.intern(a), self.intern(b)),
+            Connective::Xor(a, b) => Formula::Xor(self.intern(a), self.intern(b)),
+            Connective::Nand(a, b) => Formula::Nand(self.intern(a), self.intern(b)),
+            Connective::Nor(a, b) => Formula::Nor(self.intern(a), self.intern(b)),
+            Connective::ReverseImplicate(a, b) => {
+                Formula::ReverseImplicate(self.intern(a), self.intern(b))
+            }
+            Connective::ForAll(x, sort, inner) => {
+                Formula::ForAll(x.clone(), sort.clone(), self.intern(inner))
+            }
+            Connective::Exists(x, sort, inner) => {
+                Formula::Exists(x.clone(), sort.clone(), self.intern(inner))
+            }
+            Connective::Box(x) => Formula::Box(self.intern(x)),
+            Connective::Diamond(x) => Formula::Diamond(self.intern(x)),
+            Connective::Next(x) => Formula::Next(self.intern(x)),
+            Connective::Eventually(x) => Formula::Eventually(self.intern(x)),
+            Connective::Always(x) => Formula::Always(self.intern(x)),
+            Connective::Until(a, b) => Formula::Until(self.intern(a), self.intern(b)),
+        };
+        self.add(formula)
+    }
+
+    /// The formula `id` stands for, as a tree of its own.
+    pub fn get(&self, id: FormulaId) -> Connective {
+        let get = |x: &FormulaId| box self.get(*x);
+        match &self[id] {
+            Formula::Var(x) => Connective::Var(x.clone()),
+            Formula::Top => Connective::Top,
+            Formula::Bottom => Connective::Bottom,
+            Formula::Error => Connective::Error,
+            Formula::Predicate(p, args) => Connective::Predicate(p.clone(), args.clone()),
+            Formula::Eq(a, b) => Connective::Eq(a.clone(), b.clone()),
+            Formula::Not(x) => Connective::Not(get(x)),
+            Formula::And(a, b) => Connective::And(get(a), get(b)),
+            Formula::Or(a, b) => Connective::Or(get(a), get(b)),
+            Formula::Implicate(a, b) => Connective::Implicate(get(a), get(b)),
+            Formula::Biimplicate(a, b) => Connective::Biimplicate(get(a), get(b)),
+            Formula::Xor(a, b) => Connective::Xor(get(a), get(b)),
+            Formula::Nand(a, b) => Connective::Nand(get(a), get(b)),
+            Formula::Nor(a, b) => Connective::Nor(get(a), get(b)),
+            Formula::ReverseImplicate(a, b) => Connective::ReverseImplicate(get(a), get(b)),
+            Formula::ForAll(x, sort, inner) => {
+                Connective::ForAll(x.clone(), sort.clone(), get(inner))
+            }
+            Formula::Exists(x, sort, inner) => {
+                Connective::Exists(x.clone(), sort.clone(), get(inner))
+            }
+            Formula::Box(x) => Connective::Box(get(x)),
+            Formula::Diamond(x) => Connective::Diamond(get(x)),
+            Formula::Next(x) => Connective::Next(get(x)),
+            Formula::Eventually(x) => Connective::Eventually(get(x)),
+            Formula::Always(x) => Connective::Always(get(x)),
+            Formula::Until(a, b) => Connective::Until(get(a), get(b)),
+        }
+    }
+
+    /// Like [`Connective::substitude`], but only the formulas on the way to an
+    /// occurrence of `x` are built anew, the rest is shared with `id`.
+    pub fn substitude(&mut self, id: FormulaId, x: &str, y: &Term) -> FormulaId {
+        let formula = self[id].clone();
+        let formula = match &formula {
+            Formula::Var(name) if name == x => match y {
+                Term::Var(name) | Term::Const(name) => Formula::Var(name.clone()),
+                Term::Function(f, args) => Formula::Predicate(f.clone(), args.clone()),
+            },
+            Formula::Predicate(p, args) => Formula::Predicate(
+                p.clone(),
+                args.iter().map(|arg| arg.substitude(x, y)).collect(),
+            ),
+            Formula::Eq(a, b) => Formula::Eq(a.substitude(x, y), b.substitude(x, y)),
+            Formula::ForAll(v, _, _) | Formula::Exists(v, _, _) if v == x => return id,
+            Formula::ForAll(v, _, _) | Formula::Exists(v, _, _)
+                if y.contains(&Term::Var(v.clone())) || y.contains(&Term::Const(v.clone())) =>
+            {
+                // The quantifier may have to be renamed, which the tree version knows
+                // how to do.
+                let con = self.get(id).substitude(x, y);
+                return self.intern(&con);
+            }
+            _ => formula.map_operands(|op| self.substitude(op, x, y)),
+        };
+        self.add(formula)
+    }
+
+    /// The id of the alpha-canonical form of `id`, see [`Connective::alpha_canonical`].
+    /// Alpha-equivalent formulas get the same id.
+    pub fn alpha_canonical(&mut self, id: FormulaId) -> FormulaId {
+        self.canonical_in(id, &mut Canonical::default())
+    }
+
+    /// The alpha-canonical form of `id`, if [`Arena::alpha_canonical`] has found it,
+    /// either for `id` itself or for a formula `id` is part of outside of any
+    /// quantifier.
+    pub fn canonical(&self, id: FormulaId) -> Option<FormulaId> {
+        self.canonical.get(&id).cloned()
+    }
+
+    /// The alpha-canonical form of `id` inside the quantifiers of `scope`.
+    fn canonical_in(&mut self, id: FormulaId, scope: &mut Canonical) -> FormulaId {
+        let closed = scope.is_empty();
+        if let Some(canonical) = self.canonical.get(&id).filter(|_| closed) {
+            return *canonical;
+        }
+        let formula = match self[id].clone() {
+            Formula::Var(x) => Formula::Var(scope.rename(&x)),
+            Formula::Predicate(p, args) => {
+                Formula::Predicate(p, args.iter().map(|arg| scope.fold_term(arg)).collect())
+            }
+            Formula::Eq(a, b) => Formula::Eq(scope.fold_term(&a), scope.fold_term(&b)),
+            Formula::ForAll(x, sort, inner) => {
+                let name = scope.bind(&x);
+                let inner = self.canonical_in(inner, scope);
+                scope.unbind();
+                Formula::ForAll(name, sort, inner)
+            }
+            Formula::Exists(x, sort, inner) => {
+                let name = scope.bind(&x);
+                let inner = self.canonical_in(inner, scope);
+                scope.unbind();
+                Formula::Exists(name, sort, inner)
+            }
+            formula => formula.map_operands(|op| self.canonical_in(op, scope)),
+        };
+        let canonical = self.add(formula);
+        if closed {
+            self.canonical.insert(id, canonical);
+            self.canonical.insert(canonical, canonical);
+        }
+        canonical
+    }
+
+    /// Every ground term occurring as, or inside, an argument of a predicate, in the
+    /// order of [`Connective::ground_terms`].
+    pub fn ground_terms(&self, id: FormulaId) -> Vec<Term> {
+        fn terms(term: &Term, set: &mut IndexSet<Term>) {
+            if let Term::Function(_, args) = term {
+                for arg in args {
+                    terms(arg, set);
+                }
+            }
+            if term.is_ground() {
+                set.insert(term.clone());
+            }
+        }
+
+        let mut set = IndexSet::new();
+        let mut stack = vec![id];
+        while let Some(id) = stack.pop() {
+            match &self[id] {
+                Formula::Predicate(_, args) => args.iter().for_each(|arg| terms(arg, &mut set)),
+                Formula::Eq(a, b) => {
+                    terms(a, &mut set);
+                    terms(b, &mut set);
+                }
+                formula => stack.extend(formula.operands().into_iter().rev()),
+            }
+        }
+        set.into_iter().collect()
+    }
+
+    /// Evaluates the formula with the propositions assigned by `variables`, which is
+    /// what [`Connective::solve`] does, with the same treatment of formulas that
+    /// aren't propositional. Each distinct subformula is evaluated once.
+    pub fn solve(&self, id: FormulaId, variables: &IndexMap<String, bool>) -> bool {
+        self.solve_memo(id, variables, &mut HashMap::new())
+    }
+
+    /// [`Arena::solve`], reusing and extending the values in `memo`, which must have
+    /// been found under the same assignment.
+    pub(crate) fn solve_memo(
+        &self,
+        id: FormulaId,
+        variables: &IndexMap<String, bool>,
+        memo: &mut HashMap<FormulaId, bool>,
+    ) -> bool {
+        if let Some(value) = memo.get(&id) {
+            return *value;
+        }
+        let mut now = |x: &FormulaId| self.solve_memo(*x, variables, memo);
+        let value = match &self[id] {
+            Formula::Var(x) => variables.get(x).cloned().unwrap_or(false),
+            Formula::Top => true,
+            Formula::Bottom | Formula::Error => false,
+            Formula::Predicate(_, _) | Formula::ForAll(_, _, _) | Formula::Exists(_, _, _) => false,
+            Formula::Eq(a, b) => a == b,
+            Formula::Box(_) | Formula::Diamond(_) => false,
+            Formula::Next(_) => false,
+            Formula::Eventually(x) | Formula::Always(x) => now(x),
+            Formula::Until(_, b) => now(b),
+            Formula::Not(x) => !now(x),
+            Formula::And(a, b) => now(a) && now(b),
+            Formula::Or(a, b) => now(a) || now(b),
+            Formula::Implicate(a, b) => !now(a) || now(b),
+            Formula::Biimplicate(a, b) => now(a) == now(b),
+            Formula::Xor(a, b) => now(a) != now(b),
+            Formula::Nand(a, b) => !(now(a) && now(b)),
+            Formula::Nor(a, b) => !(now(a) || now(b)),
+            Formula::ReverseImplicate(a, b) => now(a) || !now(b),
+        };
+        memo.insert(id, value);
+        value
+    }
+}
+
+impl Index<FormulaId> for Arena {
+    type Output = Formula;
+
+    fn index(&self, id: FormulaId) -> &Formula {
+        &self.formulas[id.0]
+    }
+}
+
+#[cfg(test)]
+use crate::parse::parse;
+
+#[test]
+fn interning_shares_subformulas() {
+    let mut arena = Arena::new();
+    let con = parse("(P(a) ∧ q) → (P(a) ∧ q) ∨ ¬(P(a) ∧ q)").unwrap();
+    let id = arena.intern(&con);
+    assert_eq!(arena.get(id), con);
+    // P(a), q, P(a) ∧ q, ¬, ∨, →
+    assert_eq!(arena.len(), 6);
+    assert_eq!(arena.intern(&con), id);
+    assert_eq!(arena.len(), 6);
+    let commuted = arena.intern(&parse("q ∧ P(a)").unwrap());
+    assert_ne!(arena[id].operands()[0], commuted);
+}
+
+#[test]
+fn substitution_shares_untouched_parts() {
+    let mut arena = Arena::new();
+    let con = match parse("∀x (P(x) ∧ (q ∨ r) ∧ ∃y R(x, y))").unwrap() {
+        Connective::ForAll(_, _, body) => *body,
+        _ => unreachable!(),
+    };
+    let id = arena.intern(&con);
+    let before = arena.len();
+    let a = Term::Const("a".to_string());
+    let substituted = arena.substitude(id, "x", &a);
+    assert_eq!(arena.get(substituted), con.substitude("x", &a));
+    // P(a), R(a, y), ∃y R(a, y) and the two conjunctions; q ∨ r is shared.
+    assert_eq!(arena.len(), before + 5);
+
+    for (x, y) in &[("x", "y"), ("y", "x"), ("z", "x")] {
+        let y = Term::Var(y.to_string());
+        let substituted = arena.substitude(id, x, &y);
+        assert_eq!(arena.get(substituted), con.substitude(x, &y));
+    }
+}
+
+#[test]
+fn alpha_equivalent_formulas_share_a_canonical_id() {
+    let mut arena = Arena::new();
+    let a = arena.intern(&parse("∀x ∃y R(x, y)").unwrap());
+    let b = arena.intern(&parse("∀y ∃x R(y, x)").unwrap());
+    let c = arena.intern(&parse("∀x ∃y R(y, x)").unwrap());
+    assert_ne!(a, b);
+    assert_eq!(arena.alpha_canonical(a), arena.alpha_canonical(b));
+    assert_ne!(arena.alpha_canonical(a), arena.alpha_canonical(c));
+    let canonical = arena.alpha_canonical(a);
+    assert_eq!(arena.alpha_canonical(canonical), canonical);
+    assert_eq!(
+        arena.get(canonical),
+        parse("∀x ∃y R(x, y)").unwrap().alpha_canonical()
+    );
+
+    // Operands outside of any quantifier are canonicalised along with the formula.
+    let con = arena.intern(&parse("∀z P(z) ∧ ¬∃y Q(y)").unwrap());
+    let negated = arena.intern(&parse("∃y Q(y)").unwrap());
+    assert_eq!(arena.canonical(negated), None);
+    arena.alpha_canonical(con);
+    let renamed = arena.intern(&parse("∃x Q(x)").unwrap());
+    assert_eq!(
+        arena.canonical(negated),
+        Some(arena.alpha_canonical(renamed))
+    );
+}
+
+#[test]
+fn arena_agrees_with_trees() {
+    let mut arena = Arena::new();
+    for src in &[
+        "(p ∧ q) ∨ ¬(p ∧ q) ↔ r",
+        "p ⊕ (q ↑ r) ← (p ↓ ◇q)",
+        "P(f(a), b) ∧ ∀x (a ≈ g(x)) ∨ b ≈ c",
+    ] {
+        let con = parse(src).unwrap();
+        let id = arena.intern(&con);
+        assert_eq!(arena.ground_terms(id), con.ground_terms());
+        for perm in crate::all_permutations(&con.all_variables()) {
+            assert_eq!(
+                arena.solve(id, &perm),
+                con.solve(&perm),
+                "{} at {:?}",
+                src,
+                perm
+            );
+        }
+    }
+}
@@ -1,16 +1,56 @@
 type Con = Box<Connective>;
 
+/// An argument of a predicate. Names bound by an enclosing quantifier are
+/// variables, all other names are constants.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Term {
+    Var(String),
+    Const(String),
+    Function(String, Vec<Term>),
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Connective {
     Var(String),
-    Predicate(String, Vec<String>),
+    Top,
+    Bottom,
+    /// Stands in for input that failed to parse, see
+    /// [`ParseOptions::parse_recovering`](crate::ParseOptions::parse_recovering).
+    Error,
+    Predicate(String, Vec<Term>),
+    Eq(Term, Term),
     Not(Con),
     And(Con, Con),
     Or(Con, Con),
     Implicate(Con, Con),
     Biimplicate(Con, Con),
-    ForAll(String, Con),
-    Exists(String, Con),
-    // All(Vec<Connective>),
-    // Consequence(Con, Con),
+    Xor(Con, Con),
+    Nand(Con, Con),
+    Nor(Con, Con),
+    ReverseImplicate(Con, Con),
+    /// Binds a variable, optionally restricted to the terms of a sort as in
+    /// `∀x:Person`.
+    ForAll(String, Option<String>, Con),
+    Exists(String, Option<String>, Con),
+    /// Necessarily, true in every accessible world of a Kripke model.
+    Box(Con),
+    /// Possibly, true in some accessible world of a Kripke model.
+    Diamond(Con),
+    /// True if the operand holds in the next state of a trace, see
+    /// [`Connective::solve_trace`](crate::Connective::solve_trace).
+    Next(Con),
+    /// True if the operand holds now or in some later state.
+    Eventually(Con),
+    /// True if the operand holds now and in every later state.
+    Always(Con),
+    /// True if the right side holds at some point, and the left side holds in every
+    /// state before it.
+    Until(Con, Con),
+}
+
+/// An argument `A, B / C`: the conclusion should follow from the premises.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Argument {
+    pub premises: Vec<Connective>,
+    pub conclusion: Connective,
 }
@@ -0,0 +1,460 @@
+use crate::ast::{Connective, Term};
+use crate::parse::{ParseError, ParseOptions, Span};
+use crate::visit::{rebuild_connective, Fold};
+use crate::{captured_by, fresh_name, names};
+use indexmap::IndexMap;
+use std::mem::discriminant;
+
+/// A named formula with parameters, as in
+/// `let Trans(R) := ∀x ∀y ∀z (R(x, y) ∧ R(y, z) → R(x, z))`.
+///
+/// A parameter stands for a name, and may be used in the body as a proposition, a
+/// predicate or function symbol, or a constant.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Definition {
+    pub name: String,
+    pub parameters: Vec<String>,
+    pub body: Connective,
+}
+
+impl Definition {
+    /// The body with every parameter replaced by the matching argument, or `None`
+    /// when the number of arguments is wrong or a parameter used as a predicate or
+    /// function symbol is given something other than a name.
+    ///
+    /// A proposition parameter given `f(a)` becomes the predicate `f(a)`.
+    pub fn apply(&self, args: &[Term]) -> Option<Connective> {
+        if args.len() != self.parameters.len() {
+            return None;
+        }
+        let mut substitution = Substitution {
+            parameters: &self.parameters,
+            args,
+            bound: vec![],
+            failed: false,
+        };
+        let con = substitution.fold_connective(&self.body);
+        if substitution.failed {
+            None
+        } else {
+            Some(con)
+        }
+    }
+
+    /// The arguments that [`apply`](Definition::apply) turns into `con`, if any.
+    pub fn fold(&self, con: &Connective) -> Option<Vec<Term>> {
+        let mut matcher = Matcher {
+            parameters: &self.parameters,
+            bindings: vec![None; self.parameters.len()],
+        };
+        if !matcher.connective(&self.body, con) {
+            return None;
+        }
+        let args: Vec<_> = matcher.bindings.into_iter().collect::<Option<_>>()?;
+        // The matcher doesn't know about parameters shadowed by a quantifier, so make
+        // sure the arguments really give back `con`.
+        if self.apply(&args).as_ref() == Some(con) {
+            Some(args)
+        } else {
+            None
+        }
+    }
+
+    /// The definition applied to `args`, written `Name(a, b)`, or `Name` when it
+    /// has no parameters.
+    pub fn pretty_application(&self, args: &[Term]) -> String {
+        if args.is_empty() {
+            self.name.clone()
+        } else {
+            Connective::Predicate(self.name.clone(), args.to_vec()).pretty()
+        }
+    }
+}
+
+struct Substitution<'a> {
+    parameters: &'a [String],
+    args: &'a [Term],
+    /// Names bound by the quantifiers enclosing the current position, which hide a
+    /// parameter of the same name.
+    bound: Vec<String>,
+    /// Set when a parameter used as a symbol is given something other than a name.
+    failed: bool,
+}
+
+impl<'a> Substitution<'a> {
+    fn lookup(&self, name: &str) -> Option<&'a Term> {
+        if self.bound.iter().any(|x| x == name) {
+            return None;
+        }
+        let i = self.parameters.iter().position(|p| p == name)?;
+        Some(&self.args[i])
+    }
+
+    /// Whether a quantifier binding `x` around `inner` would capture a variable of
+    /// an argument substituted into `inner`.
+    fn captures(&self, x: &str, inner: &Connective) -> bool {
+        let names = names(inner);
+        self.parameters
+            .iter()
+            .filter(|p| *p != x && names.contains(*p))
+            .any(|p| matches!(self.lookup(p), Some(arg) if captured_by(arg, x)))
+    }
+
+    /// The name a predicate or function symbol is replaced by.
+    fn symbol(&mut self, name: &str) -> String {
+        match self.lookup(name) {
+            None => name.to_string(),
+            Some(Term::Var(x)) | Some(Term::Const(x)) => x.clone(),
+            Some(Term::Function(_, _)) => {
+                self.failed = true;
+                name.to_string()
+            }
+        }
+    }
+}
+
+impl<'a> Fold for Substitution<'a> {
+    fn fold_connective(&mut self, con: &Connective) -> Connective {
+        match con {
+            Connective::Var(p) => match self.lookup(p) {
+                None => con.clone(),
+                Some(Term::Var(x)) | Some(Term::Const(x)) => Connective::Var(x.clone()),
+                Some(Term::Function(f, args)) => Connective::Predicate(f.clone(), args.clone()),
+            },
+            Connective::Predicate(p, args) => Connective::Predicate(
+                self.symbol(p),
+                args.iter().map(|arg| self.fold_term(arg)).collect(),
+            ),
+            Connective::ForAll(x, sort, inner) | Connective::Exists(x, sort, inner)
+                if self.captures(x, inner) =>
+            {
+                let fresh = fresh_name(x, inner, |fresh| {
+                    self.parameters.iter().any(|p| p == fresh)
+                        || self.args.iter().any(|arg| captured_by(arg, fresh))
+                });
+                let renamed = inner.substitude(x, &Term::Var(fresh.clone()));
+                self.bound.push(fresh.clone());
+                let inner = box self.fold_connective(&renamed);
+                self.bound.pop();
+                match con {
+                    Connective::ForAll(_, _, _) => Connective::ForAll(fresh, sort.clone(), inner),
+                    _ => Connective::Exists(fresh, sort.clone(), inner),
+                }
+            }
+            Connective::ForAll(x, _, _) | Connective::Exists(x, _, _) => {
+                self.bound.push(x.clone());
+                let con = rebuild_connective(self, con);
+                self.bound.pop();
+                con
+            }
+            _ => rebuild_connective(self, con),
+        }
+    }
+
+    fn fold_term(&mut self, term: &Term) -> Term {
+        match term {
+            Term::Var(_) => term.clone(),
+            Term::Const(c) => self.lookup(c).unwrap_or(term).clone(),
+            Term::Function(f, args) => Term::Function(
+                self.symbol(f),
+                args.iter().map(|arg| self.fold_term(arg)).collect(),
+            ),
+        }
+    }
+}
+
+/// Matches a formula against the body of a definition, with the parameters as
+/// pattern variables.
+struct Matcher<'a> {
+    parameters: &'a [String],
+    bindings: Vec<Option<Term>>,
+}
+
+impl<'a> Matcher<'a> {
+    fn parameter(&self, name: &str) -> Option<usize> {
+        self.parameters.iter().position(|p| p == name)
+    }
+
+    fn bind(&mut self, i: usize, term: Term) -> bool {
+        match &self.bindings[i] {
+            Some(bound) => *bound == term,
+            None => {
+                self.bindings[i] = Some(term);
+                true
+            }
+        }
+    }
+
+    fn symbol(&mut self, pattern: &str, name: &str) -> bool {
+        match self.parameter(pattern) {
+            Some(i) => self.bind(i, Term::Const(name.to_string())),
+            None => pattern == name,
+        }
+    }
+
+    fn term(&mut self, pattern: &Term, term: &Term) -> bool {
+        match (pattern, term) {
+            (Term::Const(p), _) if self.parameter(p).is_some() => {
+                let i = self.parameter(p).unwrap();
+                self.bind(i, term.clone())
+            }
+            (Term::Function(f, pargs), Term::Function(g, args)) => {
+                self.symbol(f, g) && self.terms(pargs, args)
+            }
+            _ => pattern == term,
+        }
+    }
+
+    fn terms(&mut self, patterns: &[Term], terms: &[Term]) -> bool {
+        patterns.len() == terms.len()
+            && patterns
+                .iter()
+                .zip(terms)
+                .all(|(pattern, term)| self.term(pattern, term))
+    }
+
+    fn connective(&mut self, pattern: &Connective, con: &Connective) -> bool {
+        match (pattern, con) {
+            (Connective::Var(p), _) if self.parameter(p).is_some() => {
+                let i = self.parameter(p).unwrap();
+                match con {
+                    Connective::Var(x) => self.bind(i, Term::Const(x.clone())),
+                    Connective::Predicate(f, args) => {
+                        self.bind(i, Term::Function(f.clone(), args.clone()))
+                    }
+                    _ => false,
+                }
+            }
+            (Connective::Predicate(p, pargs), Connective::Predicate(q, args)) => {
+                self.symbol(p, q) && self.terms(pargs, args)
+            }
+            (Connective::Eq(a, b), Connective::Eq(c, d)) => self.term(a, c) && self.term(b, d),
+            (Connective::ForAll(x, s, a), Connective::ForAll(y, t, b))
+            | (Connective::Exists(x, s, a), Connective::Exists(y, t, b)) => {
+                x == y && s == t && self.connective(a, b)
+            }
+            _ => {
+                let (patterns, operands) = (pattern.operands(), con.operands());
+                if discriminant(pattern) != discriminant(con) {
+                    false
+                } else if patterns.is_empty() {
+                    pattern == con
+                } else {
+                    patterns
+                        .into_iter()
+                        .zip(operands)
+                        .all(|(pattern, con)| self.connective(pattern, con))
+                }
+            }
+        }
+    }
+}
+
+/// The error for a definition whose head, ending at `end`, isn't a name with
+/// parameters.
+fn head_error(src: &str, end: usize) -> ParseError {
+    let head = src[..end].trim();
+    let start = head.as_ptr() as usize - src.as_ptr() as usize;
+    ParseError::InvalidStatement {
+        expected: "a name or `Name(a, b)`",
+        span: Span::new(start, start + head.len()),
+    }
+}
+
+impl ParseOptions {
+    /// Adds a definition written `Name(a, b) := φ`, or `Name := φ` without
+    /// parameters. Formulas parsed with these options afterwards have `Name(s, t)`
+    /// replaced by `φ` with `s` and `t` in place of `a` and `b`, and earlier
+    /// definitions are expanded in `φ` itself.
+    ///
+    /// ```
+    /// # use solver::{parse, ParseOptions};
+    /// let mut options = ParseOptions::default();
+    /// options.define("Sym(R) := ∀x ∀y (R(x, y) → R(y, x))").unwrap();
+    /// assert_eq!(
+    ///     options.parse("Sym(Knows)").unwrap(),
+    ///     parse("∀x ∀y (Knows(x, y) → Knows(y, x))").unwrap()
+    /// );
+    /// ```
+    pub fn define(&mut self, src: &str) -> Result<(), ParseError> {
+        // The head is read like a formula, without expanding any definitions in it.
+        let plain = ParseOptions {
+            definitions: IndexMap::new(),
+            ..self.clone()
+        };
+        let (head, assign) = plain.parse_prefix(src)?;
+        if !src[assign..].starts_with(":=") {
+            let found = src[assign..].chars().next().map_or(0, char::len_utf8);
+            return Err(ParseError::InvalidStatement {
+                expected: "`:=`",
+                span: Span::new(assign, assign + found),
+            });
+        }
+        let (name, parameters) = match head {
+            Connective::Var(name) => (name, vec![]),
+            Connective::Predicate(name, args) => {
+                let parameters = args
+                    .into_iter()
+                    .map(|arg| match arg {
+                        Term::Var(x) | Term::Const(x) => Some(x),
+                        Term::Function(_, _) => None,
+                    })
+                    .collect::<Option<Vec<_>>>();
+                match parameters {
+                    Some(parameters) => (name, parameters),
+                    None => return Err(head_error(src, assign)),
+                }
+            }
+            _ => return Err(head_error(src, assign)),
+        };
+
+        // A parameter hides any definition of the same name inside the body.
+        let mut options = self.clone();
+        for parameter in &parameters {
+            options.definitions.remove(parameter);
+        }
+        let body = options
+            .parse(&src[assign + 2..])
+            .map_err(|err| err.offset(assign + 2))?;
+
+        self.definitions.insert(
+            name.clone(),
+            Definition {
+                name,
+                parameters,
+                body,
+            },
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+use crate::parse::{parse, Token};
+#[cfg(test)]
+use crate::PrettyOptions;
+
+#[cfg(test)]
+fn transitivity() -> ParseOptions {
+    let mut options = ParseOptions::default();
+    options
+        .define("Trans(R) := \\x\\y\\z (R(x,y) & R(y,z) > R(x,z))")
+        .unwrap();
+    options
+}
+
+#[test]
+fn definitions_expand_when_parsing() {
+    let mut options = transitivity();
+    assert_eq!(
+        options.parse("Trans(Less) ∧ Less(a, b)").unwrap(),
+        parse("∀x ∀y ∀z (Less(x, y) ∧ Less(y, z) → Less(x, z)) ∧ Less(a, b)").unwrap()
+    );
+
+    options.define("Both(p, q) := p ∧ q").unwrap();
+    options
+        .define("Linked := Both(r, E(a, b)) ∧ Trans(E)")
+        .unwrap();
+    assert_eq!(
+        options.parse("¬Linked").unwrap(),
+        parse("¬(r ∧ E(a, b) ∧ ∀x ∀y ∀z (E(x, y) ∧ E(y, z) → E(x, z)))").unwrap()
+    );
+    assert_eq!(
+        options.parse("Both(r, f(c))").unwrap(),
+        parse("r ∧ f(c)").unwrap()
+    );
+
+    // Wrong arities and bound names are left alone.
+    assert_eq!(
+        options.parse("Trans ∨ Both(r)").unwrap(),
+        parse("Trans ∨ Both(r)").unwrap()
+    );
+    options.define("Loves(x) := ∃x Likes(x, x)").unwrap();
+    assert_eq!(
+        options.parse("Loves(bob)").unwrap(),
+        parse("∃x Likes(x, x)").unwrap()
+    );
+
+    // A quantifier in the body doesn't capture the variables of an argument.
+    options.define("D(a) := ∀x P(x, a)").unwrap();
+    assert_eq!(
+        options.parse("∀x D(x)").unwrap(),
+        parse("∀x ∀x1 P(x1, x)").unwrap()
+    );
+
+    assert!(options.define("Trans(R := p").is_err());
+    assert!(options.define("Trans(R, ) := p").is_err());
+    let err = options.define("Bad(R) := R &").unwrap_err();
+    assert_eq!(err.span().start, 13);
+}
+
+#[test]
+fn definitions_fold_when_printing() {
+    let options = transitivity();
+    let trans = options.definitions["Trans"].clone();
+    let con = options.parse("Trans(Less) ∨ ¬Trans(More)").unwrap();
+    assert_eq!(
+        trans.fold(&parse("Trans(Less)").unwrap()),
+        None,
+        "only expansions fold"
+    );
+
+    let pretty = PrettyOptions {
+        definitions: vec![trans],
+        ..PrettyOptions::default()
+    };
+    assert_eq!(con.pretty_with(&pretty), "Trans(Less) ∨ ¬Trans(More)");
+    assert_eq!(
+        con.pretty_latex_with(&pretty),
+        "Trans(Less) \\lor \\neg Trans(More)"
+    );
+    assert_eq!(options.parse(&con.pretty_with(&pretty)).unwrap(), con);
+    assert_ne!(con.pretty(), con.pretty_with(&pretty));
+}
+
+#[test]
+fn malformed_definitions() {
+    let mut options = transitivity();
+    let head = |start, end| ParseError::InvalidStatement {
+        expected: "a name or `Name(a, b)`",
+        span: Span::new(start, end),
+    };
+    let assign = |start, end| ParseError::InvalidStatement {
+        expected: "`:=`",
+        span: Span::new(start, end),
+    };
+    for (src, expected) in &[
+        (
+            "Trans(R := p",
+            ParseError::InvalidArgumentList(Token::Colon, Span::new(8, 9)),
+        ),
+        (
+            "f(x) :=",
+            ParseError::UnexpectedToken(Token::Eof, Span::new(7, 7)),
+        ),
+        ("f(x) p", assign(5, 6)),
+        ("f(x)", assign(4, 4)),
+        ("f(g(x)) := p", head(0, 7)),
+        ("p ∧ q := r", head(0, 7)),
+        (
+            "f(x) := P(x) := p",
+            ParseError::UnexpectedToken(Token::Colon, Span::new(13, 14)),
+        ),
+    ] {
+        assert_eq!(options.define(src), Err(expected.clone()), "{}", src);
+    }
+    // A `(` in the body is the body's business.
+    let err = options.define("f(x) := (x ∧ p").unwrap_err();
+    assert_eq!(
+        err.message("f(x) := (x ∧ p"),
+        "expected `)` to close `(` opened at column 9, found end of input"
+    );
+    assert!(!options.definitions.contains_key("f"));
+
+    // Redefining a name reads the head as written.
+    options.define("Trans(S) := S(a, a)").unwrap();
+    assert_eq!(
+        options.parse("Trans(E)").unwrap(),
+        parse("E(a, a)").unwrap()
+    );
+}
@@ -0,0 +1,487 @@
+use crate::ast::Connective;
+use crate::parse::{is_ident_char, ParseError, Span};
+use indexmap::{IndexMap, IndexSet};
+use std::collections::HashSet;
+
+/// A proposition, negated when the flag is false.
+pub type Literal = (String, bool);
+
+/// A disjunction of literals, the empty clause being ⊥.
+pub type Clause = Vec<Literal>;
+
+/// The part of a formula that can't be written as DIMACS, which only knows
+/// propositions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NotPropositional(pub Connective);
+
+/// Turns formulas into clauses, either keeping them equivalent or, when `taken` is
+/// set, introducing fresh propositions so the clauses don't grow exponentially.
+struct Converter {
+    /// The propositions of the formula being converted, which a fresh proposition
+    /// must not be named like. `None` for a plain conversion.
+    taken: Option<HashSet<String>>,
+    fresh: usize,
+    /// The clauses giving the meaning of the fresh propositions.
+    definitions: Vec<Clause>,
+}
+
+impl Converter {
+    fn fresh(&mut self) -> String {
+        loop {
+            self.fresh += 1;
+            let name = format!("_{}", self.fresh);
+            if self.taken.iter().all(|taken| !taken.contains(&name)) {
+                return name;
+            }
+        }
+    }
+
+    /// A single clause implying `clauses`, through a fresh proposition when there
+    /// is more than one of them, so that `or` doesn't have to multiply them out.
+    fn one_clause(&mut self, clauses: Vec<Clause>) -> Vec<Clause> {
+        if self.taken.is_none() || clauses.len() < 2 {
+            return clauses;
+        }
+        let name = self.fresh();
+        for mut clause in clauses {
+            clause.push((name.clone(), false));
+            self.definitions.push(clause);
+        }
+        vec![vec![(name, true)]]
+    }
+
+    /// A literal equivalent to `con`, through a fresh proposition unless it is one
+    /// already. Only used where `con` occurs both as it is and negated.
+    fn literal(&mut self, con: &Connective) -> Result<Connective, NotPropositional> {
+        let is_literal = match con {
+            Connective::Not(x) => matches!(**x, Connective::Var(_)),
+            _ => matches!(con, Connective::Var(_)),
+        };
+        if is_literal || self.taken.is_none() {
+            return Ok(con.clone());
+        }
+        let name = self.fresh();
+        for &positive in &[true, false] {
+            for mut clause in self.clauses(con, positive)? {
+                clause.push((name.clone(), !positive));
+                self.definitions.push(clause);
+            }
+        }
+        Ok(Connective::Var(name))
+    }
+
+    /// The clauses of both sides of a binary connective, each side converted either
+    /// as it is or negated.
+    fn sides(
+        &mut self,
+        a: &Connective,
+        a_positive: bool,
+        b: &Connective,
+        b_positive: bool,
+    ) -> Result<(Vec<Clause>, Vec<Clause>), NotPropositional> {
+        Ok((self.clauses(a, a_positive)?, self.clauses(b, b_positive)?))
+    }
+
+    /// The conjunction of both sides, see [`Converter::sides`].
+    fn both(
+        &mut self,
+        a: &Connective,
+        a_positive: bool,
+        b: &Connective,
+        b_positive: bool,
+    ) -> Result<Vec<Clause>, NotPropositional> {
+        let (mut left, right) = self.sides(a, a_positive, b, b_positive)?;
+        left.extend(right);
+        Ok(left)
+    }
+
+    /// The disjunction of both sides, see [`Converter::sides`], by picking one
+    /// clause from each side in all possible ways.
+    fn either(
+        &mut self,
+        a: &Connective,
+        a_positive: bool,
+        b: &Connective,
+        b_positive: bool,
+    ) -> Result<Vec<Clause>, NotPropositional> {
+        let (left, right) = self.sides(a, a_positive, b, b_positive)?;
+        let (left, right) = (self.one_clause(left), self.one_clause(right));
+        Ok(left
+            .iter()
+            .flat_map(|a| {
+                right
+                    .iter()
+                    .map(move |b| a.iter().chain(b).cloned().collect())
+            })
+            .collect())
+    }
+
+    /// The clauses of `con`, or of `¬con` when `positive` is false.
+    fn clauses(
+        &mut self,
+        con: &Connective,
+        positive: bool,
+    ) -> Result<Vec<Clause>, NotPropositional> {
+        Ok(match (con, positive) {
+            (Connective::Var(p), _) => vec![vec![(p.clone(), positive)]],
+            (Connective::Top, true) | (Connective::Bottom, false) => vec![],
+            (Connective::Top, false) | (Connective::Bottom, true) => vec![vec![]],
+            (Connective::Not(x), _) => self.clauses(x, !positive)?,
+            (Connective::And(a, b), true) => self.both(a, true, b, true)?,
+            (Connective::And(a, b), false) => self.either(a, false, b, false)?,
+            (Connective::Or(a, b), true) => self.either(a, true, b, true)?,
+            (Connective::Or(a, b), false) => self.both(a, false, b, false)?,
+            (Connective::Implicate(a, b), true) => self.either(a, false, b, true)?,
+            (Connective::Implicate(a, b), false) => self.both(a, true, b, false)?,
+            (Connective::ReverseImplicate(a, b), true) => self.either(a, true, b, false)?,
+            (Connective::ReverseImplicate(a, b), false) => self.both(a, false, b, true)?,
+            (Connective::Nand(a, b), true) => self.either(a, false, b, false)?,
+            (Connective::Nand(a, b), false) => self.both(a, true, b, true)?,
+            (Connective::Nor(a, b), true) => self.both(a, false, b, false)?,
+            (Connective::Nor(a, b), false) => self.either(a, true, b, true)?,
+            (Connective::Biimplicate(a, b), _) | (Connective::Xor(a, b), _) => {
+                // Both sides are used twice, so name them rather than copy them.
+                let (a, b) = (self.literal(a)?, self.literal(b)?);
+                let same = match con {
+                    Connective::Biimplicate(_, _) => positive,
+                    _ => !positive,
+                };
+                let mut clauses = self.either(&a, !same, &b, true)?;
+                clauses.extend(self.either(&a, same, &b, false)?);
+                clauses
+            }
+            (Connective::Predicate(_, _), _)
+            | (Connective::Eq(_, _), _)
+            | (Connective::ForAll(_, _, _), _)
+            | (Connective::Exists(_, _, _), _)
+            | (Connective::Box(_), _)
+            | (Connective::Diamond(_), _)
+            | (Connective::Next(_), _)
+            | (Connective::Eventually(_), _)
+            | (Connective::Always(_), _)
+            | (Connective::Until(_, _), _)
+            | (Connective::Error, _) => return Err(NotPropositional(con.clone())),
+        })
+    }
+}
+
+/// Merges repeated literals and repeated clauses, and leaves out clauses that are
+/// always true.
+fn simplify(clauses: Vec<Clause>) -> Vec<Clause> {
+    let mut simplified: Vec<Clause> = vec![];
+    for clause in clauses {
+        let literals: IndexSet<Literal> = clause.into_iter().collect();
+        let tautology = literals
+            .iter()
+            .any(|(p, positive)| literals.contains(&(p.clone(), !positive)));
+        let clause = literals.into_iter().collect();
+        if !tautology && !simplified.contains(&clause) {
+            simplified.push(clause);
+        }
+    }
+    simplified
+}
+
+/// Converts a propositional formula into an equivalent conjunction of clauses by
+/// distributing ∨ over ∧. Repeated literals are merged and clauses that are always
+/// true are left out.
+///
+/// Distributing copies subformulas, so the result can be exponentially larger than
+/// `con`, as for `(a ∧ b) ∨ (c ∧ d) ∨ …`. [`tseitin`] avoids that.
+pub fn cnf(con: &Connective) -> Result<Vec<Clause>, NotPropositional> {
+    let mut converter = Converter {
+        taken: None,
+        fresh: 0,
+        definitions: vec![],
+    };
+    Ok(simplify(converter.clauses(con, true)?))
+}
+
+/// Converts a propositional formula into clauses which are satisfiable exactly
+/// when it is, in size linear in `con`.
+///
+/// Where [`cnf`] would copy a subformula, it is replaced by a fresh proposition
+/// `_1`, `_2` and so on, with clauses making it imply the subformula, or be
+/// equivalent to it under `↔` and `⊕`. Clauses for the other propositions are the
+/// same as those of [`cnf`], so formulas which don't need any fresh propositions
+/// give the same clauses.
+pub fn tseitin(con: &Connective) -> Result<Vec<Clause>, NotPropositional> {
+    let mut converter = Converter {
+        taken: Some(con.all_variables().into_iter().collect()),
+        fresh: 0,
+        definitions: vec![],
+    };
+    let mut clauses = converter.clauses(con, true)?;
+    clauses.extend(converter.definitions);
+    Ok(simplify(clauses))
+}
+
+/// Writes a propositional formula as a DIMACS CNF problem.
+///
+/// The clauses are those of [`tseitin`]. Propositions are numbered in the order they
+/// first appear in the clauses, and a comment line `c 1 = p` for each of them
+/// records the original names, which [`parse`] reads back.
+pub fn write(con: &Connective) -> Result<String, NotPropositional> {
+    let clauses = tseitin(con)?;
+    let mut numbers = IndexMap::new();
+    for (p, _) in clauses.iter().flatten() {
+        let n = numbers.len() + 1;
+        numbers.entry(p.clone()).or_insert(n);
+    }
+
+    let mut out = format!("c {}\n", con.pretty());
+    for (p, n) in &numbers {
+        out += &format!("c {} = {}\n", n, p);
+    }
+    out += &format!("p cnf {} {}\n", numbers.len(), clauses.len());
+    for clause in &clauses {
+        for (p, positive) in clause {
+            let sign = if *positive { "" } else { "-" };
+            out += &format!("{}{} ", sign, numbers[p]);
+        }
+        out += "0\n";
+    }
+    Ok(out)
+}
+
+/// Joins `items` with `op` the way the parser groups a chain of it, or gives
+/// `empty` when there are none.
+fn chain(
+    items: Vec<Connective>,
+    op: fn(Box<Connective>, Box<Connective>) -> Connective,
+    empty: Connective,
+) -> Connective {
+    let mut items = items.into_iter();
+    match items.next() {
+        Some(first) => items.fold(first, |all, item| op(box all, box item)),
+        None => empty,
+    }
+}
+
+/// Reads a DIMACS CNF problem as a conjunction of disjunctions.
+///
+/// Variables are named by comment lines `c 1 = p`, as written by [`write`], and
+/// otherwise called `x1`, `x2` and so on. Input stops at a line starting with `%`,
+/// which ends the problems of some benchmark collections.
+pub fn parse(src: &str) -> Result<Connective, ParseError> {
+    let mut names = IndexMap::new();
+    let mut variables = None;
+    let mut clauses = vec![];
+    let mut clause = vec![];
+
+    let mut line_start = 0;
+    for line in src.split('\n') {
+        let offset = line_start;
+        line_start += line.len() + 1;
+        let invalid = |expected, start: usize, len: usize| ParseError::InvalidStatement {
+            expected,
+            span: Span::new(offset + start, offset + start + len),
+        };
+
+        let words: Vec<_> = line.split_whitespace().collect();
+        match &words[..] {
+            [] => {}
+            [first, ..] if first.starts_with('%') => break,
+            ["c", n, "=", name]
+                if n.parse::<usize>().is_ok() && name.chars().all(is_ident_char) =>
+            {
+                names.insert(n.parse::<usize>().unwrap(), name.to_string());
+            }
+            ["c", ..] => {}
+            ["p", "cnf", n, _] if variables.is_none() => {
+                let n = n.parse::<usize>().map_err(|_| {
+                    invalid("the number of variables", line.find(n).unwrap(), n.len())
+                })?;
+                variables = Some(n);
+            }
+            ["p", ..] => {
+                return Err(invalid(
+                    "one `p cnf <variables> <clauses>` line",
+                    0,
+                    line.len(),
+                ))
+            }
+            words => {
+                let variables =
+                    variables.ok_or_else(|| invalid("a `p cnf` line", 0, line.len()))?;
+                let mut column = 0;
+                for word in words {
+                    column += line[column..].find(word).unwrap();
+                    let literal = word
+                        .parse::<isize>()
+                        .ok()
+                        .filter(|literal| literal.unsigned_abs() <= variables)
+                        .ok_or_else(|| {
+                            invalid("a literal of a declared variable", column, word.len())
+                        })?;
+                    column += word.len();
+
+                    if literal == 0 {
+                        clauses.push(chain(clause, Connective::Or, Connective::Bottom));
+                        clause = vec![];
+                        continue;
+                    }
+                    let n = literal.unsigned_abs();
+                    let name = names.get(&n).cloned().unwrap_or_else(|| format!("x{}", n));
+                    let var = Connective::Var(name);
+                    clause.push(if literal > 0 {
+                        var
+                    } else {
+                        Connective::Not(box var)
+                    });
+                }
+            }
+        }
+    }
+    if !clause.is_empty() {
+        return Err(ParseError::InvalidStatement {
+            expected: "`0` to end the clause",
+            span: Span::new(src.len(), src.len()),
+        });
+    }
+
+    Ok(chain(clauses, Connective::And, Connective::Top))
+}
+
+#[cfg(test)]
+use crate::all_permutations;
+#[cfg(test)]
+use crate::parse::parse as parse_formula;
+
+#[cfg(test)]
+fn equivalent(a: &Connective, b: &Connective) -> bool {
+    let mut variables: IndexSet<String> = a.all_variables().into_iter().collect();
+    variables.extend(b.all_variables());
+    let variables: Vec<_> = variables.into_iter().collect();
+    all_permutations(&variables)
+        .iter()
+        .all(|assignment| a.solve(assignment) == b.solve(assignment))
+}
+
+/// Whether `b` holds for some values of the propositions only it has exactly when
+/// `a` holds.
+#[cfg(test)]
+fn extends(a: &Connective, b: &Connective) -> bool {
+    let variables = a.all_variables();
+    let extra: Vec<_> = b
+        .all_variables()
+        .into_iter()
+        .filter(|p| !variables.contains(p))
+        .collect();
+    all_permutations(&variables).iter().all(|assignment| {
+        let b_holds = all_permutations(&extra).into_iter().any(|more| {
+            let mut assignment = assignment.clone();
+            assignment.extend(more);
+            b.solve(&assignment)
+        });
+        a.solve(assignment) == b_holds
+    })
+}
+
+#[cfg(test)]
+fn clauses_to_formula(clauses: &[Clause]) -> Connective {
+    let literal = |(p, positive): &Literal| {
+        let var = Connective::Var(p.clone());
+        if *positive {
+            var
+        } else {
+            Connective::Not(box var)
+        }
+    };
+    let clauses = clauses
+        .iter()
+        .map(|clause| {
+            chain(
+                clause.iter().map(literal).collect(),
+                Connective::Or,
+                Connective::Bottom,
+            )
+        })
+        .collect();
+    chain(clauses, Connective::And, Connective::Top)
+}
+
+#[test]
+fn cnf_conversion() {
+    assert_eq!(
+        cnf(&parse_formula("(p ∨ q) ∧ ¬r").unwrap()).unwrap(),
+        vec![
+            vec![("p".to_string(), true), ("q".to_string(), true)],
+            vec![("r".to_string(), false)],
+        ]
+    );
+    assert_eq!(
+        cnf(&parse_formula("p ∨ ¬p").unwrap()).unwrap(),
+        Vec::<Clause>::new()
+    );
+    assert_eq!(
+        cnf(&parse_formula("p ∧ ⊥").unwrap()).unwrap(),
+        vec![vec![("p".to_string(), true)], vec![]]
+    );
+    assert!(cnf(&parse_formula("∀x P(x)").unwrap()).is_err());
+
+    let con = parse_formula("¬(a ↔ b) ∨ (c ⊕ (d → e))").unwrap();
+    assert!(equivalent(&con, &clauses_to_formula(&cnf(&con).unwrap())));
+}
+
+#[test]
+fn tseitin_stays_small() {
+    let src = (1..=8)
+        .map(|i| format!("(a{} ∧ b{})", i, i))
+        .collect::<Vec<_>>()
+        .join(" ∨ ");
+    let con = parse_formula(&src).unwrap();
+    assert_eq!(cnf(&con).unwrap().len(), 256);
+    assert!(tseitin(&con).unwrap().len() <= 3 * 8);
+
+    for src in &[
+        "(a ∧ b) ∨ (c ∧ d)",
+        "¬((a ∧ b) ↔ (c ∨ ¬d))",
+        "((a ↔ b) ↔ c) ⊕ (a ↑ (b ∧ _1))",
+        "(p ∨ q) ∧ ¬p",
+    ] {
+        let con = parse_formula(src).unwrap();
+        let clauses = tseitin(&con).unwrap();
+        assert!(extends(&con, &clauses_to_formula(&clauses)), "{}", src);
+    }
+    // Clause-shaped formulas need no fresh propositions.
+    let con = parse_formula("(p ∨ q) ∧ (¬p ∨ r) ∧ ¬q").unwrap();
+    assert_eq!(tseitin(&con).unwrap(), cnf(&con).unwrap());
+}
+
+#[test]
+fn write_and_parse_back() {
+    for src in &[
+        "(p ∧ q) → r",
+        "¬(a ↔ b) ∨ (c ⊕ d)",
+        "(a ↑ b) ∧ (c ↓ d) ∧ (e ← f)",
+        "⊤",
+        "p ∧ ¬p",
+    ] {
+        let con = parse_formula(src).unwrap();
+        let dimacs = write(&con).unwrap();
+        let back = parse(&dimacs).unwrap_or_else(|err| panic!("{}", err.render(&dimacs)));
+        assert!(extends(&con, &back), "{}\n{}", src, dimacs);
+    }
+
+    assert_eq!(
+        write(&parse_formula("(p ∨ q) ∧ ¬p").unwrap()).unwrap(),
+        "c (p ∨ q) ∧ ¬p\nc 1 = p\nc 2 = q\np cnf 2 2\n1 2 0\n-1 0\n"
+    );
+}
+
+#[test]
+fn parse_benchmark_style_input() {
+    let src = "c A small instance\np cnf 3 2\n1 -3\n 0 2 3 -1 0\n%\n0\n";
+    assert_eq!(
+        parse(src).unwrap(),
+        parse_formula("(x1 ∨ ¬x3) ∧ (x2 ∨ x3 ∨ ¬x1)").unwrap()
+    );
+
+    let err = parse("p cnf 2 1\n1 3 0\n").unwrap_err();
+    assert_eq!(err.span().line_col("p cnf 2 1\n1 3 0\n"), (2, 3));
+    assert!(parse("1 2 0\n").is_err());
+    assert!(parse("p cnf 2 1\n1 2\n").is_err());
+    let src = "p cnf 1 1\n-9223372036854775808 0\n";
+    assert_eq!(parse(src).unwrap_err().span().line_col(src), (2, 1));
+}
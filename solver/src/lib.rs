@@ -1,256 +1,301 @@
 #![feature(box_syntax, slice_patterns, exclusive_range_pattern)]
 
+use crate::arena::Arena;
+use crate::visit::{rebuild_connective, walk_connective, walk_term, Fold, Visitor};
 use indexmap::{IndexMap, IndexSet};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
+mod alpha;
+pub mod arena;
 mod ast;
+mod definition;
+pub mod dimacs;
 mod parse;
+mod precedence;
+mod problem;
+mod smtlib;
+mod sort;
+mod symbols;
+mod temporal;
+mod tptp;
 pub mod tableau;
-
-pub use crate::ast::Connective;
-pub use crate::parse::{parse, ParseError};
+pub mod visit;
+
+pub use crate::ast::{Argument, Connective, Term};
+pub use crate::definition::Definition;
+pub use crate::parse::{
+    parse, parse_argument, parse_argument_recovering, parse_prefix, parse_recovering, ParseError,
+    ParseOptions, Span, Token,
+};
+pub use crate::precedence::{Assoc, Operator, PrecedenceTable};
+pub use crate::problem::{parse_problems, Problem};
+pub use crate::sort::{Signature, SortError};
+pub use crate::tptp::parse_tptp;
 
 impl Connective {
+    /// The propositions together with the names used in terms that aren't bound,
+    /// as used for the columns of a truth table. See [`Connective::propositions`],
+    /// [`Connective::constants`] and [`Connective::free_vars`] to tell them apart.
     pub fn all_variables(&self) -> Vec<String> {
-        let mut set = IndexSet::new();
-        self.all_variables_helper(&mut HashSet::new(), &mut set);
+        let mut variables = Variables {
+            bound: vec![],
+            set: IndexSet::new(),
+        };
+        variables.visit_connective(self);
 
-        set.iter().rev().cloned().collect()
+        variables.set.iter().rev().cloned().collect()
     }
 
-    fn all_variables_helper(&self, ignore: &mut HashSet<String>, set: &mut IndexSet<String>) {
-        match self {
-            Connective::Not(x) => x.all_variables_helper(ignore, set),
-            Connective::ForAll(r, x) | Connective::Exists(r, x) => {
-                let mut ignore = ignore.clone();
-                ignore.insert(r.clone());
-                x.all_variables_helper(&mut ignore, set)
-            }
-            Connective::Var(x) => {
-                if !ignore.contains(x) {
-                    set.insert(x.to_string());
-                }
-            }
-            Connective::Predicate(_, args) => {
-                for arg in args {
-                    if !ignore.contains(arg) {
-                        set.insert(arg.to_string());
-                    }
-                }
-            }
-            Connective::And(a, b)
-            | Connective::Or(a, b)
-            | Connective::Implicate(a, b)
-            | Connective::Biimplicate(a, b) => {
-                a.all_variables_helper(ignore, set);
-                b.all_variables_helper(ignore, set);
-            }
-        }
-    }
-    pub fn all_atomics(&self) -> Vec<Connective> {
-        let mut set = IndexSet::new();
-        self.all_atomics_helper(&mut HashSet::new(), &mut set);
+    /// Every ground term occurring as, or inside, an argument of a predicate.
+    pub fn ground_terms(&self) -> Vec<Term> {
+        let mut ground_terms = GroundTerms(IndexSet::new());
+        ground_terms.visit_connective(self);
 
-        set.iter().rev().cloned().collect()
+        ground_terms.0.into_iter().collect()
     }
 
-    fn all_atomics_helper(&self, ignore: &mut HashSet<Connective>, set: &mut IndexSet<Connective>) {
-        match self {
-            Connective::Not(x) => x.all_atomics_helper(ignore, set),
-            Connective::ForAll(r, x) | Connective::Exists(r, x) => {
-                let mut ignore = ignore.clone();
-                ignore.insert(Connective::Var(r.clone()));
-                x.all_atomics_helper(&mut ignore, set)
-            }
-            Connective::ForAll(r, x) | Connective::Exists(r, x) => {}
-            Connective::Var(_) | Connective::Predicate(_, _) => {
-                if !ignore.contains(self) {
-                    set.insert(self.clone());
-                }
-            }
-            Connective::And(a, b)
-            | Connective::Or(a, b)
-            | Connective::Implicate(a, b)
-            | Connective::Biimplicate(a, b) => {
-                a.all_atomics_helper(ignore, set);
-                b.all_atomics_helper(ignore, set);
-            }
-        }
+    pub fn all_atomics(&self) -> Vec<Connective> {
+        let mut atomics = Atomics {
+            bound: vec![],
+            set: IndexSet::new(),
+        };
+        atomics.visit_connective(self);
+
+        atomics.set.iter().rev().cloned().collect()
     }
 
     pub fn all_sub_connectives(&self, is_first: bool) -> Vec<Connective> {
-        match self {
-            Connective::Not(x) => {
-                if is_first {
-                    let mut x_s = x.all_sub_connectives(false);
-                    x_s.push(self.clone());
-                    x_s
-                } else {
-                    x.all_sub_connectives(false)
-                }
-            }
-            Connective::Var(_) => vec![],
-            Connective::Predicate(_, _) => vec![],
-            Connective::And(a, b)
-            | Connective::Or(a, b)
-            | Connective::Implicate(a, b)
-            | Connective::Biimplicate(a, b) => {
-                let mut a_s = a.all_sub_connectives(false);
-                let mut b_s = b.all_sub_connectives(false);
-                a_s.append(&mut b_s);
-
-                a_s.push(self.clone());
-
-                a_s
-            }
-            Connective::ForAll(_, a) | Connective::Exists(_, a) => {
-                let mut sub = a.all_sub_connectives(false);
-                sub.push(self.clone());
-                sub
-            }
-        }
+        let mut sub_connectives = SubConnectives {
+            is_first,
+            list: vec![],
+        };
+        sub_connectives.visit_connective(self);
+
+        sub_connectives.list
     }
 
+    /// Evaluates the formula with the propositions assigned by `variables`, which
+    /// is also taken as a trace of one state for the temporal operators.
+    ///
+    /// Only propositional logic is evaluated properly: predicates, quantified
+    /// formulas, `□φ` and `◇φ` are always false, and `a ≈ b` holds only when `a`
+    /// and `b` are the same term. Use a [`tableau::Tableau`] or
+    /// [`tableau::ModalTableau`] for those.
     pub fn solve(&self, variables: &IndexMap<String, bool>) -> bool {
+        let now = |con: &Connective| con.solve(variables);
         match self {
-            Connective::Not(x) => !x.solve(variables),
-            Connective::Var(x) => variables.get(x).cloned().unwrap_or(false), // todo
-            Connective::Predicate(_, _) => false,                             // todo
-            Connective::And(a, b) => a.solve(variables) && b.solve(variables),
-            Connective::Or(a, b) => a.solve(variables) || b.solve(variables),
-            Connective::Implicate(a, b) => (!a.solve(variables)) || b.solve(variables),
-            Connective::Biimplicate(a, b) => a.solve(variables) == b.solve(variables),
-            Connective::ForAll(_, _) => false, // todo
-            Connective::Exists(_, _) => false, // todo
+            Connective::Var(x) => variables.get(x).cloned().unwrap_or(false),
+            Connective::Top => true,
+            Connective::Bottom | Connective::Error => false,
+            Connective::Predicate(_, _) | Connective::ForAll(_, _, _) | Connective::Exists(_, _, _) => {
+                false
+            }
+            Connective::Eq(a, b) => a == b,
+            Connective::Box(_) | Connective::Diamond(_) => false,
+            Connective::Next(_) => false,
+            Connective::Eventually(x) | Connective::Always(x) => now(x),
+            Connective::Until(_, b) => now(b),
+            Connective::Not(x) => !now(x),
+            Connective::And(a, b) => now(a) && now(b),
+            Connective::Or(a, b) => now(a) || now(b),
+            Connective::Implicate(a, b) => !now(a) || now(b),
+            Connective::Biimplicate(a, b) => now(a) == now(b),
+            Connective::Xor(a, b) => now(a) != now(b),
+            Connective::Nand(a, b) => !(now(a) && now(b)),
+            Connective::Nor(a, b) => !(now(a) || now(b)),
+            Connective::ReverseImplicate(a, b) => now(a) || !now(b),
         }
     }
 
     fn symbol(&self) -> &'static str {
         match self {
             Connective::Var(_) | Connective::Predicate(_, _) => "",
+            Connective::Eq(_, _) => "≈",
+            Connective::Top => "⊤",
+            Connective::Bottom => "⊥",
+            Connective::Error => "?",
             Connective::Not(_) => "¬",
             Connective::And(_, _) => "∧",
             Connective::Or(_, _) => "∨",
             Connective::Implicate(_, _) => "→",
             Connective::Biimplicate(_, _) => "↔",
-            Connective::ForAll(_, _) => "∀",
-            Connective::Exists(_, _) => "∃",
+            Connective::Xor(_, _) => "⊕",
+            Connective::Nand(_, _) => "↑",
+            Connective::Nor(_, _) => "↓",
+            Connective::ReverseImplicate(_, _) => "←",
+            Connective::ForAll(_, _, _) => "∀",
+            Connective::Exists(_, _, _) => "∃",
+            Connective::Box(_) => "□",
+            Connective::Diamond(_) => "◇",
+            Connective::Next(_) => "X",
+            Connective::Eventually(_) => "F",
+            Connective::Always(_) => "G",
+            Connective::Until(_, _) => "U",
         }
     }
 
     fn latex_symbol(&self) -> &'static str {
         match self {
             Connective::Var(_) | Connective::Predicate(_, _) => "",
+            Connective::Eq(_, _) => "\\approx",
+            Connective::Top => "\\top",
+            Connective::Bottom => "\\bot",
+            Connective::Error => "?",
             Connective::Not(_) => "\\neg",
             Connective::And(_, _) => "\\land",
             Connective::Or(_, _) => "\\lor",
             Connective::Implicate(_, _) => "\\to",
             Connective::Biimplicate(_, _) => "\\leftrightarrow",
-            Connective::ForAll(_, _) => "\\forall",
-            Connective::Exists(_, _) => "\\exists",
+            Connective::Xor(_, _) => "\\oplus",
+            Connective::Nand(_, _) => "\\uparrow",
+            Connective::Nor(_, _) => "\\downarrow",
+            Connective::ReverseImplicate(_, _) => "\\leftarrow",
+            Connective::ForAll(_, _, _) => "\\forall",
+            Connective::Exists(_, _, _) => "\\exists",
+            Connective::Box(_) => "\\Box",
+            Connective::Diamond(_) => "\\Diamond",
+            Connective::Next(_) => "\\mathsf{X}",
+            Connective::Eventually(_) => "\\mathsf{F}",
+            Connective::Always(_) => "\\mathsf{G}",
+            Connective::Until(_, _) => "\\mathsf{U}",
         }
     }
 
     pub fn is_atomic(&self) -> bool {
-        match self {
-            Connective::Var(_) | Connective::Predicate(_, _) => true,
-            _ => false,
-        }
+        matches!(
+            self,
+            Connective::Var(_)
+                | Connective::Predicate(_, _)
+                | Connective::Eq(_, _)
+                | Connective::Top
+                | Connective::Bottom
+                | Connective::Error
+        )
     }
 
     pub fn precedence(&self) -> usize {
-        match self {
-            Connective::Var(_) | Connective::Predicate(_, _) => 0,
-            Connective::ForAll(_, _) => 1,
-            Connective::Exists(_, _) => 1,
-            Connective::Not(_) => 2,
-            Connective::And(_, _) => 3,
-            Connective::Or(_, _) => 4,
-            Connective::Implicate(_, _) => 5,
-            Connective::Biimplicate(_, _) => 5,
-        }
+        PrecedenceTable::default().of(self).0
     }
 
+    /// Whether a chain of this connective can be written without parentheses in
+    /// the default table, which holds for atoms and for every operator that groups
+    /// one way or the other, but not for `↑` and `↓`.
     pub fn associative(&self) -> bool {
-        match self {
-            Connective::Var(_) | Connective::Predicate(_, _) => true,
-            Connective::Not(_) => true,
-            Connective::And(_, _) => true,
-            Connective::Or(_, _) => true,
-            Connective::Implicate(_, _) => false,
-            Connective::Biimplicate(_, _) => false,
-            Connective::ForAll(_, _) => false,
-            Connective::Exists(_, _) => false,
-        }
+        PrecedenceTable::default().of(self).1 != Assoc::None
     }
 
     pub fn pretty(&self) -> String {
-        self.pretty_helper(9999)
+        self.pretty_with(&PrettyOptions::default())
     }
 
-    fn pretty_helper(&self, precedence: usize) -> String {
-        let own_precedence = self.precedence();
-        let s = match self {
-            Connective::Not(x) => format!("{}{}", self.symbol(), x.pretty_helper(own_precedence)),
-            Connective::Predicate(x, y) => format!("{}({})", x, y.join(", ")),
-            Connective::Var(x) => x.clone(),
-            Connective::And(a, b)
-            | Connective::Or(a, b)
-            | Connective::Implicate(a, b)
-            | Connective::Biimplicate(a, b) => format!(
-                "{} {} {}",
-                a.pretty_helper(own_precedence),
-                self.symbol(),
-                b.pretty_helper(own_precedence)
-            ),
-            Connective::ForAll(a, b) | Connective::Exists(a, b) => {
-                format!("{}{} {}", self.symbol(), a, b.pretty_helper(own_precedence))
-            }
-        };
-        if self.associative() && own_precedence <= precedence || own_precedence < precedence {
-            s
-        } else {
-            format!("({})", s)
-        }
+    pub fn pretty_with(&self, options: &PrettyOptions) -> String {
+        self.pretty_helper(options, false, usize::max_value())
     }
 
     pub fn pretty_latex(&self) -> String {
-        self.pretty_latex_helper(9999)
+        self.pretty_latex_with(&PrettyOptions::default())
     }
 
-    fn pretty_latex_helper(&self, precedence: usize) -> String {
-        let own_precedence = self.precedence();
+    pub fn pretty_latex_with(&self, options: &PrettyOptions) -> String {
+        self.pretty_helper(options, true, usize::max_value())
+    }
+
+    fn pretty_helper(&self, options: &PrettyOptions, latex: bool, max_level: usize) -> String {
+        if let Some(folded) = options.fold(self) {
+            return folded;
+        }
+        let table = &options.precedence;
+        let (own_level, assoc) = table.of(self);
+        let symbol = if latex {
+            self.latex_symbol()
+        } else {
+            self.symbol()
+        };
         let s = match self {
-            Connective::Not(x) => format!(
-                "{} {}",
-                self.latex_symbol(),
-                x.pretty_latex_helper(own_precedence)
-            ),
             Connective::Var(x) => x.clone(),
-            Connective::Predicate(x, y) => format!("{}({})", x, y.join(", ")),
-            Connective::And(a, b)
-            | Connective::Or(a, b)
-            | Connective::Implicate(a, b)
-            | Connective::Biimplicate(a, b) => format!(
-                "{} {} {}",
-                a.pretty_latex_helper(own_precedence),
-                self.latex_symbol(),
-                b.pretty_latex_helper(own_precedence)
-            ),
-            Connective::ForAll(a, b) | Connective::Exists(a, b) => format!(
-                "{}{} {}",
-                self.latex_symbol(),
-                a,
-                b.pretty_latex_helper(own_precedence)
-            ),
+            Connective::Top | Connective::Bottom | Connective::Error => symbol.to_string(),
+            Connective::Predicate(x, y) => format!("{}({})", x, Term::pretty_list(y)),
+            Connective::Eq(a, b) => format!("{} {} {}", a.pretty(), symbol, b.pretty()),
+            Connective::ForAll(_, _, _) | Connective::Exists(_, _, _) => {
+                let (binders, body) = self.quantifier_block(options.collapse_quantifiers);
+                format!(
+                    "{}{}{} {}",
+                    symbol,
+                    if latex { " " } else { "" },
+                    pretty_binders(&binders),
+                    body.pretty_helper(options, latex, own_level)
+                )
+            }
+            _ => match &self.operands()[..] {
+                [x] => {
+                    let space = match self {
+                        Connective::Not(_) | Connective::Box(_) | Connective::Diamond(_)
+                            if !latex =>
+                        {
+                            ""
+                        }
+                        _ => " ",
+                    };
+                    format!(
+                        "{}{}{}",
+                        symbol,
+                        space,
+                        x.pretty_helper(options, latex, own_level)
+                    )
+                }
+                [a, b] => {
+                    let (left_level, right_level) = table.operand_levels(own_level, assoc);
+                    // An operand on the same level that doesn't associate is
+                    // parenthesized whichever side it is on.
+                    let operand = |x: &Connective, level: usize| {
+                        let level = match table.of(x) {
+                            (x_level, Assoc::None) if x_level == own_level => own_level - 1,
+                            _ => level,
+                        };
+                        x.pretty_helper(options, latex, level)
+                    };
+                    format!(
+                        "{} {} {}",
+                        operand(a, left_level),
+                        symbol,
+                        operand(b, right_level)
+                    )
+                }
+                _ => unreachable!("only atoms and quantifiers have no or other operands"),
+            },
         };
-        if self.associative() && own_precedence <= precedence || own_precedence < precedence {
+        if own_level <= max_level {
             s
         } else {
             format!("({})", s)
         }
     }
 
+    /// The names bound by a quantifier with their sorts, and its body. With
+    /// `collapse` set, directly nested quantifiers of the same kind are gathered
+    /// into one block.
+    fn quantifier_block(&self, collapse: bool) -> (Vec<(&str, Option<&str>)>, &Connective) {
+        let (mut binders, mut body) = match self {
+            Connective::ForAll(x, sort, body) | Connective::Exists(x, sort, body) => {
+                (vec![(x.as_str(), sort.as_ref().map(String::as_str))], &**body)
+            }
+            _ => return (vec![], self),
+        };
+        if collapse {
+            loop {
+                match (self, body) {
+                    (Connective::ForAll(_, _, _), Connective::ForAll(x, sort, inner))
+                    | (Connective::Exists(_, _, _), Connective::Exists(x, sort, inner)) => {
+                        binders.push((x, sort.as_ref().map(String::as_str)));
+                        body = inner;
+                    }
+                    _ => break,
+                }
+            }
+        }
+        (binders, body)
+    }
+
     pub fn generate_table_generic<F>(&self, mut f: F) -> Table
     where
         F: FnMut(&Connective) -> String,
@@ -258,6 +303,11 @@ impl Connective {
         let variables = self.all_variables().into_iter().collect::<Vec<_>>();
         let permutations = all_permutations(&variables);
         let sub_connectives = self.all_sub_connectives(true);
+        let mut arena = Arena::new();
+        let ids = sub_connectives
+            .iter()
+            .map(|con| arena.intern(con))
+            .collect::<Vec<_>>();
 
         let headers = if let Some(headers) = permutations
             .iter()
@@ -280,9 +330,11 @@ impl Connective {
         let rows = permutations
             .iter()
             .map(|perm| {
+                // subformulas shared between columns are only evaluated once a row
+                let mut memo = HashMap::new();
                 perm.values()
                     .cloned()
-                    .chain(sub_connectives.iter().map(|x| x.solve(perm)))
+                    .chain(ids.iter().map(|id| arena.solve_memo(*id, perm, &mut memo)))
                     .collect()
             })
             .collect();
@@ -298,41 +350,17 @@ impl Connective {
         self.generate_table_generic(|p| p.pretty())
     }
 
-    pub fn substitude(&self, x: &str, y: &str) -> Connective {
-        match self {
-            Connective::Var(xx) if x == xx => Connective::Var(y.to_string()),
-            Connective::Var(_) => self.clone(),
-            Connective::Predicate(p, args) => Connective::Predicate(
-                p.clone(),
-                args.iter()
-                    .map(|arg| {
-                        if arg == x {
-                            y.to_string()
-                        } else {
-                            arg.to_string()
-                        }
-                    })
-                    .collect(),
-            ),
-            Connective::Not(i) => Connective::Not(box i.substitude(x, y)),
-            Connective::And(a, b) => {
-                Connective::And(box a.substitude(x, y), box b.substitude(x, y))
-            }
-            Connective::Or(a, b) => Connective::Or(box a.substitude(x, y), box b.substitude(x, y)),
-            Connective::Implicate(a, b) => {
-                Connective::Implicate(box a.substitude(x, y), box b.substitude(x, y))
-            }
-            Connective::Biimplicate(a, b) => {
-                Connective::Biimplicate(box a.substitude(x, y), box b.substitude(x, y))
-            }
-            Connective::ForAll(xx, _) | Connective::Exists(xx, _) if xx == x => self.clone(),
-            Connective::ForAll(xx, inner) => {
-                Connective::ForAll(xx.to_string(), box inner.substitude(x, y))
-            }
-            Connective::Exists(xx, inner) => {
-                Connective::Exists(xx.to_string(), box inner.substitude(x, y))
-            }
-        }
+    /// Replaces the free occurrences of the variable `x` by `y`. A quantifier
+    /// whose variable is named in `y` is renamed first, so `∃y P(x, y)` with `y`
+    /// for `x` becomes `∃y1 P(y, y1)` rather than `∃y P(y, y)`.
+    pub fn substitude(&self, x: &str, y: &Term) -> Connective {
+        Substitution { x, y }.fold_connective(self)
+    }
+
+    /// Replaces every proposition or predicate that applies one of `definitions`
+    /// with the right number of arguments by its expansion.
+    pub fn expand_definitions(&self, definitions: &IndexMap<String, Definition>) -> Connective {
+        Expansion(definitions).fold_connective(self)
     }
 
     pub fn tableau_dot_graph(self, expect: bool) -> String {
@@ -378,6 +406,326 @@ impl Connective {
     }
 }
 
+impl Argument {
+    /// The starting facts of a tableau proving the argument: every premise true and
+    /// the conclusion false. The argument is valid if every branch closes.
+    pub fn tableau_start(&self) -> Vec<(Connective, bool)> {
+        self.premises
+            .iter()
+            .cloned()
+            .map(|premise| (premise, true))
+            .chain(Some((self.conclusion.clone(), false)))
+            .collect()
+    }
+
+    pub fn tableau(&self) -> tableau::Tableau {
+        tableau::Tableau::new(self.tableau_start())
+    }
+
+    /// A labelled tableau for the argument in the modal logic `system`, see
+    /// [`tableau::ModalTableau`].
+    pub fn modal_tableau(&self, system: tableau::ModalSystem) -> tableau::ModalTableau {
+        tableau::ModalTableau::new(system, self.tableau_start())
+    }
+
+    /// A tableau for the argument whose quantifiers only range over the terms of
+    /// their sort, see [`tableau::Tableau::with_signature`].
+    pub fn tableau_with(&self, signature: &Signature) -> tableau::Tableau {
+        tableau::Tableau::with_signature(self.tableau_start(), signature)
+    }
+
+    /// The formula `(A ∧ B) → C` for `A, B / C`, which is a tautology exactly when
+    /// the argument is valid, e.g. for checking it with a truth table.
+    pub fn implication(&self) -> Connective {
+        let mut premises = self.premises.iter().cloned();
+        match premises.next() {
+            Some(first) => Connective::Implicate(
+                box premises.fold(first, |all, premise| Connective::And(box all, box premise)),
+                box self.conclusion.clone(),
+            ),
+            None => self.conclusion.clone(),
+        }
+    }
+
+    pub fn tableau_dot_graph(&self) -> String {
+        self.tableau().generate_dot()
+    }
+
+    pub fn pretty(&self) -> String {
+        let premises = self
+            .premises
+            .iter()
+            .map(|premise| premise.pretty())
+            .collect::<Vec<_>>()
+            .join(", ");
+        if premises.is_empty() {
+            format!("⊨ {}", self.conclusion.pretty())
+        } else {
+            format!("{} ⊨ {}", premises, self.conclusion.pretty())
+        }
+    }
+}
+
+impl Term {
+    pub fn name(&self) -> &str {
+        match self {
+            Term::Var(name) | Term::Const(name) | Term::Function(name, _) => name,
+        }
+    }
+
+    /// The nesting depth of function applications in the term.
+    pub fn depth(&self) -> usize {
+        match self {
+            Term::Var(_) | Term::Const(_) => 0,
+            Term::Function(_, args) => 1 + args.iter().map(Term::depth).max().unwrap_or(0),
+        }
+    }
+
+    /// Whether the term contains no variables.
+    pub fn is_ground(&self) -> bool {
+        match self {
+            Term::Var(_) => false,
+            Term::Const(_) => true,
+            Term::Function(_, args) => args.iter().all(Term::is_ground),
+        }
+    }
+
+    /// Replaces the variable `x` by `y`.
+    pub fn substitude(&self, x: &str, y: &Term) -> Term {
+        match self {
+            Term::Var(xx) if xx == x => y.clone(),
+            Term::Var(_) | Term::Const(_) => self.clone(),
+            Term::Function(f, args) => {
+                Term::Function(f.clone(), args.iter().map(|arg| arg.substitude(x, y)).collect())
+            }
+        }
+    }
+
+    pub fn contains(&self, term: &Term) -> bool {
+        self == term
+            || match self {
+                Term::Function(_, args) => args.iter().any(|arg| arg.contains(term)),
+                Term::Var(_) | Term::Const(_) => false,
+            }
+    }
+
+    /// Replaces every occurrence of the subterm `from` by `to`.
+    pub fn replace(&self, from: &Term, to: &Term) -> Term {
+        if self == from {
+            return to.clone();
+        }
+        match self {
+            Term::Var(_) | Term::Const(_) => self.clone(),
+            Term::Function(f, args) => {
+                Term::Function(f.clone(), args.iter().map(|arg| arg.replace(from, to)).collect())
+            }
+        }
+    }
+
+    pub fn pretty(&self) -> String {
+        match self {
+            Term::Var(x) | Term::Const(x) => x.clone(),
+            Term::Function(f, args) => format!("{}({})", f, Term::pretty_list(args)),
+        }
+    }
+
+    fn pretty_list(terms: &[Term]) -> String {
+        terms
+            .iter()
+            .map(|term| term.pretty())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// The propositions and the names in terms that aren't bound by a quantifier, see
+/// [`Connective::all_variables`].
+struct Variables {
+    bound: Vec<String>,
+    set: IndexSet<String>,
+}
+
+impl Visitor for Variables {
+    fn visit_connective(&mut self, con: &Connective) {
+        match con {
+            Connective::Var(x) => {
+                if !self.bound.contains(x) {
+                    self.set.insert(x.clone());
+                }
+            }
+            Connective::ForAll(x, _, inner) | Connective::Exists(x, _, inner) => {
+                self.bound.push(x.clone());
+                self.visit_connective(inner);
+                self.bound.pop();
+            }
+            _ => walk_connective(self, con),
+        }
+    }
+
+    fn visit_term(&mut self, term: &Term) {
+        match term {
+            Term::Var(x) | Term::Const(x) => {
+                if !self.bound.contains(x) {
+                    self.set.insert(x.clone());
+                }
+            }
+            Term::Function(_, _) => walk_term(self, term),
+        }
+    }
+}
+
+struct GroundTerms(IndexSet<Term>);
+
+impl Visitor for GroundTerms {
+    fn visit_term(&mut self, term: &Term) {
+        walk_term(self, term);
+        if term.is_ground() {
+            self.0.insert(term.clone());
+        }
+    }
+}
+
+/// The propositions, predicates and equalities, leaving out propositions named like
+/// an enclosing bound variable.
+struct Atomics {
+    bound: Vec<String>,
+    set: IndexSet<Connective>,
+}
+
+impl Visitor for Atomics {
+    fn visit_connective(&mut self, con: &Connective) {
+        match con {
+            Connective::Var(x) => {
+                if !self.bound.contains(x) {
+                    self.set.insert(con.clone());
+                }
+            }
+            Connective::Predicate(_, _) | Connective::Eq(_, _) => {
+                self.set.insert(con.clone());
+            }
+            Connective::ForAll(x, _, inner) | Connective::Exists(x, _, inner) => {
+                self.bound.push(x.clone());
+                self.visit_connective(inner);
+                self.bound.pop();
+            }
+            _ => walk_connective(self, con),
+        }
+    }
+}
+
+/// The compound subformulas, innermost first. A negation is only listed when it is
+/// the whole formula.
+struct SubConnectives {
+    is_first: bool,
+    list: Vec<Connective>,
+}
+
+impl Visitor for SubConnectives {
+    fn visit_connective(&mut self, con: &Connective) {
+        let is_first = std::mem::replace(&mut self.is_first, false);
+        if con.is_atomic() {
+            return;
+        }
+        walk_connective(self, con);
+        match con {
+            Connective::Not(_) if !is_first => {}
+            _ => self.list.push(con.clone()),
+        }
+    }
+}
+
+/// Replaces the free occurrences of the variable `x` by `y`.
+struct Substitution<'a> {
+    x: &'a str,
+    y: &'a Term,
+}
+
+/// Whether `term` would be captured by a quantifier binding `name`.
+pub(crate) fn captured_by(term: &Term, name: &str) -> bool {
+    term.contains(&Term::Var(name.to_string())) || term.contains(&Term::Const(name.to_string()))
+}
+
+/// A variable named after `name` that doesn't occur in `inner` and isn't `taken`.
+pub(crate) fn fresh_name(name: &str, inner: &Connective, taken: impl Fn(&str) -> bool) -> String {
+    let names = names(inner);
+    (1..)
+        .map(|i| format!("{}{}", name, i))
+        .find(|fresh| !names.contains(fresh) && !taken(fresh))
+        .expect("there are infinitely many names")
+}
+
+/// Every name used in `con` for a proposition, a term or a bound variable.
+pub(crate) fn names(con: &Connective) -> HashSet<String> {
+    let mut names = Names(HashSet::new());
+    names.visit_connective(con);
+    names.0
+}
+
+impl<'a> Fold for Substitution<'a> {
+    fn fold_connective(&mut self, con: &Connective) -> Connective {
+        match con {
+            Connective::Var(x) if x == self.x => match self.y {
+                Term::Var(name) | Term::Const(name) => Connective::Var(name.clone()),
+                Term::Function(f, args) => Connective::Predicate(f.clone(), args.clone()),
+            },
+            Connective::ForAll(x, _, _) | Connective::Exists(x, _, _) if x == self.x => con.clone(),
+            Connective::ForAll(x, sort, inner) | Connective::Exists(x, sort, inner)
+                if captured_by(self.y, x) && inner.all_variables().iter().any(|v| v == self.x) =>
+            {
+                let fresh = fresh_name(x, inner, |fresh| {
+                    fresh == self.x || captured_by(self.y, fresh)
+                });
+                let renamed = inner.substitude(x, &Term::Var(fresh.clone()));
+                let inner = box self.fold_connective(&renamed);
+                match con {
+                    Connective::ForAll(_, _, _) => Connective::ForAll(fresh, sort.clone(), inner),
+                    _ => Connective::Exists(fresh, sort.clone(), inner),
+                }
+            }
+            _ => rebuild_connective(self, con),
+        }
+    }
+
+    fn fold_term(&mut self, term: &Term) -> Term {
+        term.substitude(self.x, self.y)
+    }
+}
+
+/// Every name used for a proposition, a term or a bound variable.
+struct Names(HashSet<String>);
+
+impl Visitor for Names {
+    fn visit_connective(&mut self, con: &Connective) {
+        match con {
+            Connective::Var(x) | Connective::ForAll(x, _, _) | Connective::Exists(x, _, _) => {
+                self.0.insert(x.clone());
+            }
+            _ => {}
+        }
+        walk_connective(self, con);
+    }
+
+    fn visit_term(&mut self, term: &Term) {
+        self.0.insert(term.name().to_string());
+        walk_term(self, term);
+    }
+}
+
+struct Expansion<'a>(&'a IndexMap<String, Definition>);
+
+impl<'a> Fold for Expansion<'a> {
+    fn fold_connective(&mut self, con: &Connective) -> Connective {
+        let expansion = match con {
+            Connective::Var(x) => self.0.get(x).and_then(|definition| definition.apply(&[])),
+            Connective::Predicate(p, args) => {
+                self.0.get(p).and_then(|definition| definition.apply(args))
+            }
+            _ => None,
+        };
+        expansion.unwrap_or_else(|| rebuild_connective(self, con))
+    }
+}
+
 fn indent(s: &str) -> String {
     s.split('\n')
         .map(|x| format!("\t{}", x))
@@ -385,6 +733,39 @@ fn indent(s: &str) -> String {
         .join("\n")
 }
 
+/// Binders as written after a quantifier, e.g. `x:Person, y`.
+fn pretty_binders(binders: &[(&str, Option<&str>)]) -> String {
+    binders
+        .iter()
+        .map(|(name, sort)| match sort {
+            Some(sort) => format!("{}:{}", name, sort),
+            None => name.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Settings for [`Connective::pretty_with`] and [`Connective::pretty_latex_with`].
+#[derive(Debug, Clone, Default)]
+pub struct PrettyOptions {
+    pub precedence: PrecedenceTable,
+    /// Print `∀x ∀y φ` as `∀x, y φ`.
+    pub collapse_quantifiers: bool,
+    /// Print any expansion of these definitions as the definition applied to its
+    /// arguments, trying them in order.
+    pub definitions: Vec<Definition>,
+}
+
+impl PrettyOptions {
+    fn fold(&self, con: &Connective) -> Option<String> {
+        self.definitions.iter().find_map(|definition| {
+            definition
+                .fold(con)
+                .map(|args| definition.pretty_application(&args))
+        })
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Table {
     pub headers: Vec<String>,
@@ -409,3 +790,31 @@ pub fn all_permutations(variables: &[String]) -> Vec<IndexMap<String, bool>> {
         [] => vec![IndexMap::new()],
     }
 }
+
+#[test]
+fn substitution_avoids_capture() {
+    let body = match parse("∀x ∃y P(x, y)").unwrap() {
+        Connective::ForAll(_, _, body) => *body,
+        _ => unreachable!(),
+    };
+    let y = Term::Const("y".to_string());
+    assert_eq!(body.substitude("x", &y).pretty(), "∃y1 P(y, y1)");
+    assert_eq!(body.substitude("x", &y), parse("∃y1 P(y, y1)").unwrap());
+    assert_eq!(
+        body.substitude("x", &Term::Const("a".to_string())).pretty(),
+        "∃y P(a, y)"
+    );
+
+    let body = match parse("∀x ∃y (∀y1 P(x, y, y1) ∧ Q(y))").unwrap() {
+        Connective::ForAll(_, _, body) => *body,
+        _ => unreachable!(),
+    };
+    let term = Term::Function("f".to_string(), vec![y]);
+    assert_eq!(
+        body.substitude("x", &term).pretty(),
+        "∃y2 (∀y1 P(f(y), y2, y1) ∧ Q(y2))"
+    );
+    // Nothing is renamed where `x` doesn't occur.
+    let con = parse("∃y Q(y) ∧ ∀y R(y)").unwrap();
+    assert_eq!(con.substitude("x", &term), con);
+}
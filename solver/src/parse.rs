@@ -1,188 +1,1736 @@
-use crate::ast::Connective;
+use crate::ast::{Argument, Connective, Term};
+use crate::definition::Definition;
+use crate::precedence::{Assoc, Operator, PrecedenceTable};
+use crate::visit::{walk_term_mut, MutVisitor};
+use indexmap::IndexMap;
+use std::collections::HashSet;
+use std::fmt;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Token {
     Var(String),
+    Top,
+    Bottom,
     Not,
+    Box,
+    Diamond,
+    Next,
+    Eventually,
+    Always,
+    Until,
     And,
     Or,
     Implicate,
     Biimplicate,
+    Xor,
+    Nand,
+    Nor,
+    ReverseImplicate,
+    Equals,
+    NotEquals,
     Comma,
+    Colon,
     Slash,
     OpenParen,
     CloseParen,
     ForAll,
     Exists,
+    /// `.`, which is `∃` except after the variables of a quantifier, where it may
+    /// end them instead.
+    Dot,
+    Unknown(char),
+    Eof,
 }
 
-fn lex(src: &str) -> Vec<Token> {
+impl Token {
+    fn describe(&self) -> String {
+        match self {
+            Token::Eof => "end of input".to_string(),
+            t => format!("`{}`", t),
+        }
+    }
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::Var(x) => write!(f, "{}", x),
+            Token::Top => write!(f, "⊤"),
+            Token::Bottom => write!(f, "⊥"),
+            Token::Not => write!(f, "¬"),
+            Token::Box => write!(f, "□"),
+            Token::Diamond => write!(f, "◇"),
+            Token::Next => write!(f, "X"),
+            Token::Eventually => write!(f, "F"),
+            Token::Always => write!(f, "G"),
+            Token::Until => write!(f, "U"),
+            Token::And => write!(f, "∧"),
+            Token::Or => write!(f, "∨"),
+            Token::Implicate => write!(f, "→"),
+            Token::Biimplicate => write!(f, "↔"),
+            Token::Xor => write!(f, "⊕"),
+            Token::Nand => write!(f, "↑"),
+            Token::Nor => write!(f, "↓"),
+            Token::ReverseImplicate => write!(f, "←"),
+            Token::Equals => write!(f, "≈"),
+            Token::NotEquals => write!(f, "≠"),
+            Token::Comma => write!(f, ","),
+            Token::Colon => write!(f, ":"),
+            Token::Slash => write!(f, "/"),
+            Token::OpenParen => write!(f, "("),
+            Token::CloseParen => write!(f, ")"),
+            Token::ForAll => write!(f, "∀"),
+            Token::Exists => write!(f, "∃"),
+            Token::Dot => write!(f, "."),
+            Token::Unknown(c) => write!(f, "{}", c),
+            Token::Eof => Ok(()),
+        }
+    }
+}
+
+/// A byte range into the source text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Span {
+        Span { start, end }
+    }
+
+    /// The 1-based line and column (counted in characters) of the start of the span.
+    pub fn line_col(&self, src: &str) -> (usize, usize) {
+        let before = &src[..self.start.min(src.len())];
+        let line = before.matches('\n').count() + 1;
+        let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+        (line, before[line_start..].chars().count() + 1)
+    }
+
+    pub fn column(&self, src: &str) -> usize {
+        self.line_col(src).1
+    }
+
+    fn offset(self, by: usize) -> Span {
+        Span::new(self.start + by, self.end + by)
+    }
+}
+
+type Spanned = (Token, Span);
+
+pub(crate) fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Operators spelled with more than one character, longest first. Only `==` and
+/// `!=` are read without [`ParseOptions::digraphs`].
+fn multi_char_operator(src: &str, digraphs: bool) -> Option<(Token, usize)> {
+    let operators = [
+        ("<->", Token::Biimplicate, true),
+        ("<=>", Token::Biimplicate, true),
+        ("->", Token::Implicate, true),
+        ("=>", Token::Implicate, true),
+        ("<-", Token::ReverseImplicate, true),
+        ("/\\", Token::And, true),
+        ("\\/", Token::Or, true),
+        ("[]", Token::Box, true),
+        ("<>", Token::Diamond, true),
+        ("==", Token::Equals, false),
+        ("!=", Token::NotEquals, false),
+    ];
+    operators
+        .iter()
+        .filter(|(_, _, digraph)| digraphs || !digraph)
+        .find(|(text, _, _)| src.starts_with(text))
+        .map(|(text, token, _)| (token.clone(), text.len()))
+}
+
+/// The LaTeX commands `pretty_latex` produces, along with a few common synonyms.
+fn latex_command(name: &str) -> Option<Token> {
+    Some(match name {
+        "neg" | "lnot" => Token::Not,
+        "Box" | "square" => Token::Box,
+        "Diamond" | "lozenge" => Token::Diamond,
+        "land" | "wedge" => Token::And,
+        "lor" | "vee" => Token::Or,
+        "to" | "rightarrow" | "implies" => Token::Implicate,
+        "leftrightarrow" | "iff" => Token::Biimplicate,
+        "oplus" => Token::Xor,
+        "uparrow" => Token::Nand,
+        "downarrow" => Token::Nor,
+        "leftarrow" => Token::ReverseImplicate,
+        "forall" => Token::ForAll,
+        "exists" => Token::Exists,
+        "top" => Token::Top,
+        "bot" => Token::Bottom,
+        "approx" => Token::Equals,
+        "neq" => Token::NotEquals,
+        "vdash" | "models" => Token::Slash,
+        _ => return None,
+    })
+}
+
+fn keyword(name: &str) -> Option<Token> {
+    Some(match name {
+        "not" => Token::Not,
+        "and" => Token::And,
+        "or" => Token::Or,
+        "implies" => Token::Implicate,
+        "iff" => Token::Biimplicate,
+        "xor" => Token::Xor,
+        "nand" => Token::Nand,
+        "nor" => Token::Nor,
+        "forall" => Token::ForAll,
+        "exists" => Token::Exists,
+        "next" => Token::Next,
+        "eventually" => Token::Eventually,
+        "always" => Token::Always,
+        "until" => Token::Until,
+        _ => return None,
+    })
+}
+
+/// The single letter temporal operators, read everywhere when
+/// [`ParseOptions::temporal_letters`] is set and otherwise only where a name can't
+/// stand (see [`read_temporal_letters`]).
+fn temporal_letter(name: &str) -> Option<Token> {
+    Some(match name {
+        "X" => Token::Next,
+        "F" => Token::Eventually,
+        "G" => Token::Always,
+        "U" => Token::Until,
+        _ => return None,
+    })
+}
+
+/// Turns the names `X`, `F` and `G` in front of an operand and `U` between two
+/// operands into the temporal operators, which is how `pretty` writes them, so
+/// `G (p → F q)` parses back without [`ParseOptions::temporal_letters`]. Names in
+/// a binder or sort position, and those applied to a list of terms like `G(x)`,
+/// are left alone.
+fn read_temporal_letters(tokens: &mut [Spanned]) {
+    fn starts_operand(tokens: &[Spanned]) -> bool {
+        match tokens {
+            [(Token::OpenParen, _), rest @ ..] => term_list(rest).is_none(),
+            [(token, _), ..] => matches!(
+                token,
+                Token::Var(_)
+                    | Token::Not
+                    | Token::Box
+                    | Token::Diamond
+                    | Token::ForAll
+                    | Token::Exists
+                    | Token::Dot
+                    | Token::Top
+                    | Token::Bottom
+                    | Token::Next
+                    | Token::Eventually
+                    | Token::Always
+            ),
+            [] => false,
+        }
+    }
+
+    for i in (0..tokens.len()).rev() {
+        let token = match &tokens[i].0 {
+            Token::Var(name) => match temporal_letter(name) {
+                Some(token) => token,
+                None => continue,
+            },
+            _ => continue,
+        };
+        let before = i.checked_sub(1).map(|i| &tokens[i].0);
+        let after = &tokens[i + 1..];
+        let reads = if token == Token::Until {
+            let ends_operand = matches!(
+                before,
+                Some(Token::Var(_))
+                    | Some(Token::CloseParen)
+                    | Some(Token::Top)
+                    | Some(Token::Bottom)
+            );
+            ends_operand && (starts_operand(after) || matches!(after[0].0, Token::OpenParen))
+        } else {
+            let binds = matches!(
+                before,
+                Some(Token::ForAll) | Some(Token::Exists) | Some(Token::Dot) | Some(Token::Colon)
+            );
+            !binds && starts_operand(after)
+        };
+        if reads {
+            tokens[i].0 = token;
+        }
+    }
+}
+
+fn lex(src: &str, options: &ParseOptions) -> Vec<Spanned> {
     let mut tokens = vec![];
+    let mut i = 0;
+
+    while let Some(c) = src[i..].chars().next() {
+        if let Some((token, len)) = multi_char_operator(&src[i..], options.digraphs) {
+            tokens.push((token, Span::new(i, i + len)));
+            i += len;
+            continue;
+        }
+        if c == '\\' && options.latex_commands {
+            let name_len = src[i + 1..]
+                .find(|c: char| !c.is_ascii_alphabetic())
+                .unwrap_or(src.len() - i - 1);
+            if let Some(token) = latex_command(&src[i + 1..i + 1 + name_len]) {
+                tokens.push((token, Span::new(i, i + 1 + name_len)));
+                i += 1 + name_len;
+                continue;
+            }
+            // `pretty_latex` writes the temporal operators as `\mathsf{X}` and so on
+            let mathsf = src[i..]
+                .get(..10)
+                .filter(|command| command.starts_with("\\mathsf{") && command.ends_with('}'))
+                .and_then(|command| temporal_letter(&command[8..9]));
+            if let Some(token) = mathsf {
+                tokens.push((token, Span::new(i, i + 10)));
+                i += 10;
+                continue;
+            }
+        }
 
-    for (_i, c) in src.char_indices() {
+        let single = Span::new(i, i + c.len_utf8());
+        i = single.end;
         match c {
-            '¬' | '!' => tokens.push(Token::Not),
-            '∧' | '&' | '∪' => tokens.push(Token::And),
-            '∨' | '|' | '∩' => tokens.push(Token::Or),
-            '→' | '>' => tokens.push(Token::Implicate),
-            '↔' | '=' => tokens.push(Token::Biimplicate),
-            ',' => tokens.push(Token::Comma),
-            '/' => tokens.push(Token::Slash),
-            '(' => tokens.push(Token::OpenParen),
-            ')' => tokens.push(Token::CloseParen),
-            '\\' => tokens.push(Token::ForAll),
-            '.' => tokens.push(Token::Exists),
-            ' ' => {}
-            c => tokens.push(Token::Var(c.to_string())),
+            '⊤' => tokens.push((Token::Top, single)),
+            '⊥' => tokens.push((Token::Bottom, single)),
+            '≈' => tokens.push((Token::Equals, single)),
+            '≠' => tokens.push((Token::NotEquals, single)),
+            '¬' | '!' | '~' => tokens.push((Token::Not, single)),
+            '□' | '◻' => tokens.push((Token::Box, single)),
+            '◇' | '◊' => tokens.push((Token::Diamond, single)),
+            '∧' | '&' | '∪' => tokens.push((Token::And, single)),
+            '∨' | '|' | '∩' => tokens.push((Token::Or, single)),
+            '→' | '>' => tokens.push((Token::Implicate, single)),
+            '↔' | '=' => tokens.push((Token::Biimplicate, single)),
+            '⊕' | '^' => tokens.push((Token::Xor, single)),
+            '↑' => tokens.push((Token::Nand, single)),
+            '↓' => tokens.push((Token::Nor, single)),
+            '←' | '<' => tokens.push((Token::ReverseImplicate, single)),
+            ',' => tokens.push((Token::Comma, single)),
+            ':' => tokens.push((Token::Colon, single)),
+            '/' | '⊢' | '⊨' => tokens.push((Token::Slash, single)),
+            '(' => tokens.push((Token::OpenParen, single)),
+            ')' => tokens.push((Token::CloseParen, single)),
+            '∀' | '\\' => tokens.push((Token::ForAll, single)),
+            '∃' => tokens.push((Token::Exists, single)),
+            '.' => tokens.push((Token::Dot, single)),
+            c if c.is_whitespace() => {}
+            c if is_ident_char(c) => {
+                let end = src[i..]
+                    .find(|c| !is_ident_char(c))
+                    .map(|len| i + len)
+                    .unwrap_or(src.len());
+                i = end;
+                let token = match &src[single.start..end] {
+                    name if options.temporal_letters && temporal_letter(name).is_some() => {
+                        temporal_letter(name).unwrap()
+                    }
+                    "T" | "1" if options.ascii_constants => Token::Top,
+                    "F" | "0" if options.ascii_constants => Token::Bottom,
+                    name if options.keywords && keyword(name).is_some() => keyword(name).unwrap(),
+                    name => Token::Var(name.to_string()),
+                };
+                tokens.push((token, Span::new(single.start, end)))
+            }
+            c => tokens.push((Token::Unknown(c), single)),
         }
     }
+    tokens.push((Token::Eof, Span::new(src.len(), src.len())));
+    if !options.temporal_letters {
+        read_temporal_letters(&mut tokens);
+    }
 
     tokens
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum ParseError {
-    UncosedParen(Option<Token>),
-    UnexpectedToken(Option<Token>),
-    InvalidArgumentList(Option<Token>),
+    UncosedParen { open: Span, found: Token, span: Span },
+    UnexpectedToken(Token, Span),
+    InvalidArgumentList(Token, Span),
+    /// Two operators on the same level next to each other, where one of them
+    /// doesn't associate, like `a ↑ b ↑ c`.
+    Unparenthesized { first: Token, second: Token, span: Span },
+    /// A name in a quantifier block followed by parens that could as well be its
+    /// arguments as the body, like `y (x, y)` in `∀x y (x, y)`.
+    AmbiguousBlock {
+        name: String,
+        span: Span,
+    },
+    /// A malformed statement in a problem file or TPTP input.
+    InvalidStatement { expected: &'static str, span: Span },
+}
+
+impl ParseError {
+    /// The location in the source the error points at.
+    pub fn span(&self) -> Span {
+        match self {
+            ParseError::UncosedParen { span, .. }
+            | ParseError::UnexpectedToken(_, span)
+            | ParseError::InvalidArgumentList(_, span)
+            | ParseError::Unparenthesized { span, .. }
+            | ParseError::AmbiguousBlock { span, .. }
+            | ParseError::InvalidStatement { span, .. } => *span,
+        }
+    }
+
+    /// Moves the error `by` bytes further into the source, for errors found in a
+    /// piece cut out of a larger text.
+    pub(crate) fn offset(self, by: usize) -> ParseError {
+        match self {
+            ParseError::UncosedParen { open, found, span } => ParseError::UncosedParen {
+                open: open.offset(by),
+                found,
+                span: span.offset(by),
+            },
+            ParseError::UnexpectedToken(token, span) => {
+                ParseError::UnexpectedToken(token, span.offset(by))
+            }
+            ParseError::InvalidArgumentList(token, span) => {
+                ParseError::InvalidArgumentList(token, span.offset(by))
+            }
+            ParseError::Unparenthesized {
+                first,
+                second,
+                span,
+            } => ParseError::Unparenthesized {
+                first,
+                second,
+                span: span.offset(by),
+            },
+            ParseError::AmbiguousBlock { name, span } => ParseError::AmbiguousBlock {
+                name,
+                span: span.offset(by),
+            },
+            ParseError::InvalidStatement { expected, span } => ParseError::InvalidStatement {
+                expected,
+                span: span.offset(by),
+            },
+        }
+    }
+
+    pub fn message(&self, src: &str) -> String {
+        match self {
+            ParseError::UncosedParen { open, found, .. } => format!(
+                "expected `)` to close `(` opened at column {}, found {}",
+                open.column(src),
+                found.describe()
+            ),
+            ParseError::UnexpectedToken(found, _) => format!("unexpected {}", found.describe()),
+            ParseError::InvalidArgumentList(found, _) => format!(
+                "expected `,` or `)` in argument list, found {}",
+                found.describe()
+            ),
+            ParseError::Unparenthesized { first, second, .. } => format!(
+                "{} can't follow {} without parentheses",
+                second.describe(),
+                first.describe()
+            ),
+            ParseError::AmbiguousBlock { name, .. } => format!(
+                "`{}` could be bound by the quantifier or applied to the terms after it, \
+                 write `{}.` or `{}(` to say which",
+                name, name, name
+            ),
+            ParseError::InvalidStatement { expected, .. } => format!("expected {}", expected),
+        }
+    }
+
+    /// Renders the offending line of `src` with a caret under the error location,
+    /// followed by a description of the error.
+    pub fn render(&self, src: &str) -> String {
+        let span = self.span();
+        let (line, column) = span.line_col(src);
+        let text = src.lines().nth(line - 1).unwrap_or("");
+        let width = src
+            .get(span.start..span.end)
+            .map(|s| s.chars().take_while(|&c| c != '\n').count())
+            .unwrap_or(0)
+            .max(1);
+        let gutter = line.to_string();
+
+        format!(
+            "{} | {}\n{} | {}{}\n{}",
+            gutter,
+            text,
+            " ".repeat(gutter.len()),
+            " ".repeat(column - 1),
+            "^".repeat(width),
+            self.message(src)
+        )
+    }
 }
 
 type ParseResult<T> = Result<T, ParseError>;
 
-fn parse_top(tokens: &[Token]) -> ParseResult<(Connective, &[Token])> {
-    parse3(tokens)
+fn unexpected(tokens: &[Spanned]) -> ParseError {
+    let (token, span) = tokens.first().cloned().expect("token stream ends with Eof");
+    ParseError::UnexpectedToken(token, span)
+}
+
+/// Settings that control how source text is turned into a [`Connective`].
+#[derive(Debug, Clone, Default)]
+pub struct ParseOptions {
+    pub precedence: PrecedenceTable,
+    /// Also read `T`/`1` as ⊤ and `F`/`0` as ⊥, rather than as names.
+    pub ascii_constants: bool,
+    /// Also read `X`, `F`, `G` and `U` as the temporal operators, rather than as
+    /// names. This takes precedence over `ascii_constants` for `F`. Without it they
+    /// are still operators where a name couldn't stand, as in `G (p → F q)`.
+    pub temporal_letters: bool,
+    /// Also read the words `not`, `and`, `or`, `implies`, `iff`, `xor`, `nand`,
+    /// `nor`, `forall`, `exists`, `next`, `eventually`, `always` and `until` as
+    /// operators, rather than as names.
+    pub keywords: bool,
+    /// Also read the ASCII operators `->`, `<->`, `=>`, `<=>`, `<-`, `/\`, `\/`,
+    /// `[]` and `<>`, rather than as the characters they are made of. Without it
+    /// `p /\x P(x)` is an argument concluding `∀x P(x)`.
+    pub digraphs: bool,
+    /// Also read LaTeX commands like `\neg` and `\land`, as written by
+    /// `pretty_latex`, rather than taking `\neg` as `∀neg`.
+    pub latex_commands: bool,
+    /// Definitions expanded wherever their name is applied, see
+    /// [`define`](ParseOptions::define).
+    pub definitions: IndexMap<String, Definition>,
 }
-/*
-fn parse3(tokens: &[Token]) -> ParseResult<(Connective, &[Token])> {
-    let (left, rest) = parse3(tokens)?;
 
-    match rest {
-        [Token::Comma, rest @ ..] => {
-            let mut args = vec![left];
-            let mut rest = rest;
+impl ParseOptions {
+    /// Parses `src` as a single formula, rejecting any input left over after it.
+    pub fn parse(&self, src: &str) -> ParseResult<Connective> {
+        let tokens = lex(src, self);
+        match Parser::new(self).parse_top(&tokens)? {
+            (mut con, [(Token::Eof, _)]) => {
+                free_variables(vec![&mut con]);
+                Ok(con)
+            }
+            (_, rest) => Err(unexpected(rest)),
+        }
+    }
+
+    /// Parses the longest formula at the start of `src`, returning it together with
+    /// the byte offset where parsing stopped.
+    pub fn parse_prefix(&self, src: &str) -> ParseResult<(Connective, usize)> {
+        let tokens = lex(src, self);
+        let (mut con, rest) = Parser::new(self).parse_top(&tokens)?;
+        free_variables(vec![&mut con]);
+        let end = rest.first().map(|(_, span)| span.start).unwrap_or(src.len());
+        Ok((con, end))
+    }
+
+    /// Parses an argument `A, B / C`, where `⊢` or `⊨` may be used in place of `/`
+    /// and the list of premises may be empty.
+    pub fn parse_argument(&self, src: &str) -> ParseResult<Argument> {
+        let tokens = lex(src, self);
+        let mut parser = Parser::new(self);
+        let mut premises = vec![];
+        let mut rest = &tokens[..];
+
+        if let [(Token::Slash, _), nrest @ ..] = rest {
+            rest = nrest;
+        } else {
             loop {
-                let (right, inner_rest) = parse3(rest)?;
-                args.push(right);
-                match inner_rest {
-                    [Token::Comma, inner_rest @ ..] => {
-                        rest = inner_rest;
-                    }
-                    [Token::Slash, rest @ ..] => {
-                        let (right, rest) = parse3(rest)?;
-                        let predicate = Connective::All(args);
-                        return Ok((Connective::Consequence(box predicate, box right), rest));
-                    }
-                    [] => {
-                        return Ok((Connective::All(args), &[]));
+                let (premise, nrest) = parser.parse_top(rest)?;
+                premises.push(premise);
+                match nrest {
+                    [(Token::Comma, _), nrest @ ..] => rest = nrest,
+                    [(Token::Slash, _), nrest @ ..] => {
+                        rest = nrest;
+                        break;
                     }
-                    [x, _..] => return Err(ParseError::UnexpectedToken(Some(x.clone()))),
+                    x => return Err(unexpected(x)),
                 }
             }
         }
-        [Token::Slash, rest @ ..] => {
-            let (right, rest) = parse3(rest)?;
-            return Ok((Connective::Consequence(box left, box right), rest));
+
+        match parser.parse_top(rest)? {
+            (mut conclusion, [(Token::Eof, _)]) => {
+                free_variables(premises.iter_mut().chain(Some(&mut conclusion)));
+                Ok(Argument {
+                    premises,
+                    conclusion,
+                })
+            }
+            (_, rest) => Err(unexpected(rest)),
         }
-        _ => Ok((left, rest)),
+    }
+
+    /// Parses `src` as a single formula like [`parse`](ParseOptions::parse), but
+    /// keeps going after an error instead of stopping at the first one.
+    ///
+    /// Input that fails to parse is skipped up to the next binary operator,
+    /// closing paren, `,` or `/` and replaced by [`Connective::Error`], so the
+    /// returned formula is a best-effort reading of the whole input. The errors are
+    /// in source order and empty exactly when `parse` would have succeeded.
+    pub fn parse_recovering(&self, src: &str) -> (Connective, Vec<ParseError>) {
+        let tokens = lex(src, self);
+        let mut parser = Parser::recovering(self);
+        let (mut con, _) = parser.parse_until(&tokens, &[]);
+        free_variables(vec![&mut con]);
+        (con, parser.errors.unwrap_or_default())
+    }
+
+    /// Parses an argument like [`parse_argument`](ParseOptions::parse_argument),
+    /// recovering from errors the same way as
+    /// [`parse_recovering`](ParseOptions::parse_recovering). Each premise and the
+    /// conclusion recover separately, so a mistake in one doesn't hide the others.
+    pub fn parse_argument_recovering(&self, src: &str) -> (Argument, Vec<ParseError>) {
+        let tokens = lex(src, self);
+        let mut parser = Parser::recovering(self);
+        let mut premises = vec![];
+        let mut rest = &tokens[..];
+
+        let conclusion = if let [(Token::Slash, _), rest @ ..] = rest {
+            parser.parse_until(rest, &[]).0
+        } else {
+            loop {
+                let (premise, nrest) = parser.parse_until(rest, &[Token::Comma, Token::Slash]);
+                match nrest {
+                    [(Token::Comma, _), nrest @ ..] => {
+                        premises.push(premise);
+                        rest = nrest;
+                    }
+                    [(Token::Slash, _), nrest @ ..] => {
+                        premises.push(premise);
+                        break parser.parse_until(nrest, &[]).0;
+                    }
+                    x => {
+                        // Without a `/` the last formula is taken as the conclusion.
+                        parser.report(unexpected(x));
+                        break premise;
+                    }
+                }
+            }
+        };
+
+        let mut argument = Argument {
+            premises,
+            conclusion,
+        };
+        free_variables(
+            argument
+                .premises
+                .iter_mut()
+                .chain(Some(&mut argument.conclusion)),
+        );
+        (argument, parser.errors.unwrap_or_default())
+    }
+}
+
+/// Turns constants named like a variable some quantifier of `formulas` binds into
+/// free variables, like `x` in `Q(x)` in `∀x P(x) ∧ Q(x)`, since the name is
+/// unlikely to have been meant as both.
+fn free_variables<'c>(formulas: impl IntoIterator<Item = &'c mut Connective>) {
+    let formulas: Vec<_> = formulas.into_iter().collect();
+    let bound: HashSet<String> = formulas.iter().flat_map(|con| con.bound_vars()).collect();
+    if bound.is_empty() {
+        return;
+    }
+    let mut free = FreeVariables(&bound);
+    for con in formulas {
+        free.visit_connective_mut(con);
     }
 }
-*/
 
-fn parse3(tokens: &[Token]) -> ParseResult<(Connective, &[Token])> {
-    let (left, rest) = parse2(tokens)?;
-    Ok(match rest {
-        [Token::Implicate, rest @ ..] => {
-            let (right, rest) = parse3(rest)?;
-            (Connective::Implicate(box left, box right), rest)
+struct FreeVariables<'b>(&'b HashSet<String>);
+
+impl MutVisitor for FreeVariables<'_> {
+    fn visit_term_mut(&mut self, term: &mut Term) {
+        match term {
+            Term::Const(c) if self.0.contains(c) => *term = Term::Var(c.clone()),
+            _ => walk_term_mut(self, term),
         }
-        [Token::Biimplicate, rest @ ..] => {
-            let (right, rest) = parse3(rest)?;
-            (Connective::Biimplicate(box left, box right), rest)
+    }
+}
+
+fn binary_operator(token: &Token) -> Option<Operator> {
+    match token {
+        Token::And => Some(Operator::And),
+        Token::Or => Some(Operator::Or),
+        Token::Implicate => Some(Operator::Implicate),
+        Token::Biimplicate => Some(Operator::Biimplicate),
+        Token::Xor => Some(Operator::Xor),
+        Token::Nand => Some(Operator::Nand),
+        Token::Nor => Some(Operator::Nor),
+        Token::ReverseImplicate => Some(Operator::ReverseImplicate),
+        Token::Until => Some(Operator::Until),
+        _ => None,
+    }
+}
+
+/// The variables bound by a quantifier, as read by [`parse_binders`].
+struct Binders<'a> {
+    names: Vec<(String, Option<String>)>,
+    /// Whether the block ended with a `.`, letting the body extend as far right as
+    /// possible.
+    dotted: bool,
+    rest: &'a [Spanned],
+}
+
+/// The names in a list of terms up to and including its closing paren, like the
+/// arguments of a predicate, along with whether any of the terms is an
+/// application. `None` if `tokens` don't start with such a list.
+fn term_list(tokens: &[Spanned]) -> Option<(Vec<&String>, bool, &[Spanned])> {
+    let mut names = vec![];
+    let mut nested = false;
+    let mut rest = tokens;
+    loop {
+        rest = match rest {
+            [(Token::Var(_), _), (Token::OpenParen, _), nrest @ ..] => {
+                let (inner, _, nrest) = term_list(nrest)?;
+                names.extend(inner);
+                nested = true;
+                nrest
+            }
+            [(Token::Var(name), _), nrest @ ..] => {
+                names.push(name);
+                nrest
+            }
+            _ => return None,
+        };
+        match rest {
+            [(Token::Comma, _), nrest @ ..] => rest = nrest,
+            [(Token::CloseParen, _), nrest @ ..] => return Some((names, nested, nrest)),
+            _ => return None,
         }
-        _ => (left, rest),
+    }
+}
+
+/// Whether the name starting `tokens` is bound by the quantifier whose block it
+/// follows, rather than starting its body.
+///
+/// A name followed by parens is applied to them, unless they hold something other
+/// than a list of terms, or terms that use the name itself as an argument, as in
+/// `∀x y (P(x, y))`. When they hold nothing but names including the name itself,
+/// like `∀x y (x, y)`, either reading is as likely and it is an error.
+fn joins_block(tokens: &[Spanned]) -> ParseResult<bool> {
+    let (name, span, next) = match tokens {
+        [(Token::Var(name), span), (next, _), ..] => (name, span, next),
+        _ => return Ok(false),
+    };
+    Ok(match next {
+        Token::Var(_) | Token::Comma | Token::Colon | Token::Dot => true,
+        Token::Not | Token::Box | Token::Diamond | Token::ForAll | Token::Exists => true,
+        Token::Next | Token::Eventually | Token::Always => true,
+        Token::OpenParen => match term_list(&tokens[2..]) {
+            None => true,
+            Some((names, false, rest)) if names.contains(&name) => {
+                return Err(ParseError::AmbiguousBlock {
+                    name: name.clone(),
+                    span: Span::new(span.start, tokens[tokens.len() - rest.len() - 1].1.end),
+                })
+            }
+            Some((names, _, _)) => names.contains(&name),
+        },
+        _ => false,
     })
 }
 
-fn parse2(tokens: &[Token]) -> ParseResult<(Connective, &[Token])> {
-    let (left, rest) = parse1(tokens)?;
+/// Parses the variables bound by a quantifier, either a single name or a block
+/// like `x, y, z.` or `x y z`. Each name may be followed by a sort, as in `x:Person`.
+///
+/// A further name only joins the block when it is followed by another name, a `,`,
+/// a `.`, a prefix operator or parens that can't be its arguments (see
+/// [`joins_block`]), so `∀x P(x)` and `∀x P (x)` bind only `x` while
+/// `∀x y z (φ ∧ ψ)`, `∀x y (P(x, y))` and `∀x y ¬P(x, y)` bind every name. A `.` after the
+/// last name ends the block and lets the body extend as far right as possible,
+/// unless what follows would join a block itself, which keeps `\x.y P(x, y)` meaning
+/// `∀x ∃y P(x, y)`.
+fn parse_binders(tokens: &[Spanned]) -> ParseResult<Binders<'_>> {
+    let (mut names, mut rest) = match tokens {
+        [(Token::Var(name), _), rest @ ..] => (vec![(name.clone(), None)], rest),
+        x => return Err(unexpected(x)),
+    };
 
-    Ok(match rest {
-        [Token::And, rest @ ..] => {
-            let (right, rest) = parse2(rest)?;
-            (Connective::And(box left, box right), rest)
+    loop {
+        match rest {
+            [(Token::Colon, _), (Token::Var(sort), _), nrest @ ..] => {
+                let (_, binder_sort) = names.last_mut().unwrap();
+                if binder_sort.is_some() {
+                    return Err(unexpected(rest));
+                }
+                *binder_sort = Some(sort.clone());
+                rest = nrest;
+            }
+            [(Token::Colon, _), x @ ..] => return Err(unexpected(x)),
+            [(Token::Comma, _), (Token::Var(name), _), nrest @ ..] => {
+                names.push((name.clone(), None));
+                rest = nrest;
+            }
+            [(Token::Var(name), _), ..] if joins_block(rest)? => {
+                names.push((name.clone(), None));
+                rest = &rest[1..];
+            }
+            [(Token::Dot, _), nrest @ ..] => {
+                let dotted = !joins_block(nrest)?;
+                let rest = if dotted { nrest } else { rest };
+                return Ok(Binders {
+                    names,
+                    dotted,
+                    rest,
+                });
+            }
+            _ => {
+                return Ok(Binders {
+                    names,
+                    dotted: false,
+                    rest,
+                })
+            }
         }
-        [Token::Or, rest @ ..] => {
-            let (right, rest) = parse2(rest)?;
-            (Connective::Or(box left, box right), rest)
+    }
+}
+
+/// Tokens that may end a formula without being part of it.
+fn ends_formula(token: &Token) -> bool {
+    matches!(
+        token,
+        Token::CloseParen | Token::Comma | Token::Slash | Token::Eof
+    )
+}
+
+/// Skips tokens up to the next binary operator or token ending a formula that
+/// isn't nested inside parens opened along the way.
+fn synchronize(tokens: &[Spanned]) -> &[Spanned] {
+    let mut depth = 0;
+    for (i, (token, _)) in tokens.iter().enumerate() {
+        match token {
+            Token::Eof => return &tokens[i..],
+            Token::OpenParen => depth += 1,
+            Token::CloseParen if depth > 0 => depth -= 1,
+            token if depth == 0 && (binary_operator(token).is_some() || ends_formula(token)) => {
+                return &tokens[i..]
+            }
+            _ => {}
         }
-        _ => (left, rest),
-    })
+    }
+    unreachable!("token stream ends with Eof")
 }
 
-fn parse1(tokens: &[Token]) -> ParseResult<(Connective, &[Token])> {
-    Ok(match tokens {
-        [Token::Not, rest @ ..] => {
-            let (expr, rest) = parse1(rest)?;
-            (Connective::Not(box expr), rest)
+struct Parser<'a> {
+    options: &'a ParseOptions,
+    /// Names bound by the quantifiers enclosing the current position.
+    bound: Vec<String>,
+    /// The errors found so far when recovering from them, `None` when parsing
+    /// should stop at the first one.
+    errors: Option<Vec<ParseError>>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(options: &'a ParseOptions) -> Parser<'a> {
+        Parser {
+            options,
+            bound: vec![],
+            errors: None,
         }
-        [Token::ForAll, name, rest @ ..] => {
-            let name = if let Token::Var(name) = name {
-                name
-            } else {
-                return Err(ParseError::UnexpectedToken(Some(name.clone())));
-            };
+    }
 
-            let (right, rest) = parse1(rest)?;
+    fn recovering(options: &'a ParseOptions) -> Parser<'a> {
+        Parser {
+            errors: Some(vec![]),
+            ..Parser::new(options)
+        }
+    }
 
-            (Connective::ForAll(name.clone(), box right), rest)
+    fn report(&mut self, err: ParseError) {
+        if let Some(errors) = &mut self.errors {
+            // Whatever contains a broken construct often trips over the same token,
+            // so only the first error at each location is kept.
+            if errors.last().map(ParseError::span) != Some(err.span()) {
+                errors.push(err);
+            }
         }
-        [Token::Exists, name, rest @ ..] => {
-            let name = match name {
-                Token::Var(name) => name,
-                x => Err(ParseError::UnexpectedToken(Some(x.clone())))?,
+    }
+
+    /// Reports `err` and replaces the construct starting at `tokens` with an error
+    /// node when recovering, and fails with `err` otherwise.
+    fn recover<'t>(
+        &mut self,
+        err: ParseError,
+        tokens: &'t [Spanned],
+    ) -> ParseResult<(Connective, &'t [Spanned])> {
+        if self.errors.is_none() {
+            return Err(err);
+        }
+        self.report(err);
+        Ok((Connective::Error, synchronize(tokens)))
+    }
+
+    /// Parses a formula when recovering, reporting and skipping any stray tokens
+    /// after it up to the end of input or one of `stop`.
+    fn parse_until<'t>(&mut self, tokens: &'t [Spanned], stop: &[Token]) -> (Connective, &'t [Spanned]) {
+        let (mut con, mut rest) = self.parse_top(tokens).expect("recovering parser doesn't fail");
+        loop {
+            match rest {
+                [(Token::Eof, _)] => return (con, rest),
+                [(token, _), ..] if stop.contains(token) => return (con, rest),
+                [_, nrest @ ..] => {
+                    self.report(unexpected(rest));
+                    let (ncon, nrest) = self
+                        .parse_operators(con, synchronize(nrest), usize::max_value(), None)
+                        .expect("recovering parser doesn't fail");
+                    con = ncon;
+                    rest = nrest;
+                }
+                [] => unreachable!("token stream ends with Eof"),
+            }
+        }
+    }
+
+    /// The formula a name or application stands for on its own, which is the
+    /// expansion of a definition when it applies one with the right arity.
+    fn atom(&self, term: Term) -> Connective {
+        let expansion = match &term {
+            Term::Const(name) => self.options.definitions.get(name).and_then(|d| d.apply(&[])),
+            Term::Function(name, args) => {
+                self.options.definitions.get(name).and_then(|d| d.apply(args))
+            }
+            Term::Var(_) => None,
+        };
+        match (expansion, term) {
+            (Some(con), _) => con,
+            (None, Term::Function(p, args)) => Connective::Predicate(p, args),
+            (None, Term::Var(x)) | (None, Term::Const(x)) => Connective::Var(x),
+        }
+    }
+
+    fn parse_top<'t>(&mut self, tokens: &'t [Spanned]) -> ParseResult<(Connective, &'t [Spanned])> {
+        self.parse_binary(tokens, usize::max_value())
+    }
+
+    /// Parses operators binding at `max_level` or tighter by precedence climbing.
+    fn parse_binary<'t>(
+        &mut self,
+        tokens: &'t [Spanned],
+        max_level: usize,
+    ) -> ParseResult<(Connective, &'t [Spanned])> {
+        let (left, rest) = self.parse_unary(tokens)?;
+        self.parse_operators(left, rest, max_level, None)
+    }
+
+    /// Continues [`parse_binary`](Parser::parse_binary) after its first operand.
+    /// `previous` is the operator `left` is the right operand of, if any, which an
+    /// operator on its level may not follow if either of them doesn't associate.
+    fn parse_operators<'t>(
+        &mut self,
+        mut left: Connective,
+        mut rest: &'t [Spanned],
+        max_level: usize,
+        mut previous: Option<(&'t Token, usize, Assoc)>,
+    ) -> ParseResult<(Connective, &'t [Spanned])> {
+        let table = &self.options.precedence;
+
+        loop {
+            let (op, after_op) = match rest {
+                [(token, _), after_op @ ..] => match binary_operator(token) {
+                    Some(op) => (op, after_op),
+                    None if self.errors.is_some() && !ends_formula(token) => {
+                        self.report(unexpected(rest));
+                        rest = synchronize(after_op);
+                        continue;
+                    }
+                    None => break,
+                },
+                [] => break,
             };
+            let (level, assoc) = table.get(op);
+            if level > max_level {
+                break;
+            }
+            if let Some((first, previous_level, previous_assoc)) = previous {
+                if previous_level == level
+                    && (previous_assoc == Assoc::None || assoc == Assoc::None)
+                {
+                    let (second, span) = rest[0].clone();
+                    let err = ParseError::Unparenthesized {
+                        first: first.clone(),
+                        second,
+                        span,
+                    };
+                    if self.errors.is_none() {
+                        return Err(err);
+                    }
+                    self.report(err);
+                }
+            }
+            previous = Some((&rest[0].0, level, assoc));
+            let (_, right_level) = table.operand_levels(level, assoc);
+            let (right, nrest) = self.parse_unary(after_op)?;
+            let (right, nrest) = self.parse_operators(right, nrest, right_level, previous)?;
+            left = op.apply(left, right);
+            rest = nrest;
+        }
 
-            let (right, rest) = parse1(rest)?;
+        Ok((left, rest))
+    }
 
-            (Connective::Exists(name.clone(), box right), rest)
+    fn parse_unary<'t>(&mut self, tokens: &'t [Spanned]) -> ParseResult<(Connective, &'t [Spanned])> {
+        match self.parse_operand(tokens) {
+            Ok(result) => Ok(result),
+            Err(err) => self.recover(err, tokens),
         }
-        [Token::Var(x), Token::OpenParen, Token::Var(arg), rest @ ..] => {
-            let mut rest = rest;
-            let mut args = vec![arg.clone()];
-            loop {
+    }
+
+    fn parse_operand<'t>(&mut self, tokens: &'t [Spanned]) -> ParseResult<(Connective, &'t [Spanned])> {
+        let table = &self.options.precedence;
+        let (not_level, _) = table.get(Operator::Not);
+        let (quantifier_level, _) = table.get(Operator::Quantifier);
+
+        Ok(match tokens {
+            [(Token::Not, _), rest @ ..] => {
+                let (expr, rest) = self.parse_binary(rest, not_level)?;
+                (Connective::Not(box expr), rest)
+            }
+            [(Token::Box, _), rest @ ..] => {
+                let (expr, rest) = self.parse_binary(rest, not_level)?;
+                (Connective::Box(box expr), rest)
+            }
+            [(Token::Diamond, _), rest @ ..] => {
+                let (expr, rest) = self.parse_binary(rest, not_level)?;
+                (Connective::Diamond(box expr), rest)
+            }
+            [(Token::Next, _), rest @ ..] => {
+                let (expr, rest) = self.parse_binary(rest, not_level)?;
+                (Connective::Next(box expr), rest)
+            }
+            [(Token::Eventually, _), rest @ ..] => {
+                let (expr, rest) = self.parse_binary(rest, not_level)?;
+                (Connective::Eventually(box expr), rest)
+            }
+            [(Token::Always, _), rest @ ..] => {
+                let (expr, rest) = self.parse_binary(rest, not_level)?;
+                (Connective::Always(box expr), rest)
+            }
+            [(Token::ForAll, _), rest @ ..] => {
+                let Binders {
+                    names,
+                    dotted,
+                    rest,
+                } = parse_binders(rest)?;
+                let level = if dotted { usize::max_value() } else { quantifier_level };
+                let (right, rest) = self.parse_scoped(&names, rest, level)?;
+
+                let con = names
+                    .into_iter()
+                    .rev()
+                    .fold(right, |con, (name, sort)| Connective::ForAll(name, sort, box con));
+                (con, rest)
+            }
+            [(Token::Exists, _), rest @ ..] | [(Token::Dot, _), rest @ ..] => {
+                let Binders {
+                    names,
+                    dotted,
+                    rest,
+                } = parse_binders(rest)?;
+                let level = if dotted { usize::max_value() } else { quantifier_level };
+                let (right, rest) = self.parse_scoped(&names, rest, level)?;
+
+                let con = names
+                    .into_iter()
+                    .rev()
+                    .fold(right, |con, (name, sort)| Connective::Exists(name, sort, box con));
+                (con, rest)
+            }
+            [(Token::Var(_), _), ..] => {
+                let (term, rest) = self.parse_term(tokens)?;
                 match rest {
-                    [Token::Comma, Token::Var(arg), nrest @ ..] => {
-                        args.push(arg.clone());
-                        rest = nrest;
+                    [(Token::Equals, _), rest @ ..] => {
+                        let (right, rest) = self.parse_term(rest)?;
+                        (Connective::Eq(term, right), rest)
                     }
-                    [Token::CloseParen, nrest @ ..] => {
-                        rest = nrest;
-                        break;
+                    [(Token::NotEquals, _), rest @ ..] => {
+                        let (right, rest) = self.parse_term(rest)?;
+                        (Connective::Not(box Connective::Eq(term, right)), rest)
                     }
-                    x => return Err(ParseError::InvalidArgumentList(x.get(0).cloned())),
+                    _ => (self.atom(term), rest),
                 }
             }
-            (Connective::Predicate(x.clone(), args), rest)
-        }
-        [Token::Var(x), rest @ ..] => (Connective::Var(x.to_string()), rest),
-        [Token::OpenParen, rest @ ..] => {
-            let (expr, rest) = parse_top(rest)?;
-            match rest {
-                [Token::CloseParen, rest @ ..] => (expr, rest),
-                x => return Err(ParseError::UncosedParen(x.get(0).cloned())),
+            [(Token::Top, _), rest @ ..] => (Connective::Top, rest),
+            [(Token::Bottom, _), rest @ ..] => (Connective::Bottom, rest),
+            [(Token::OpenParen, open), rest @ ..] => {
+                let (expr, rest) = self.parse_top(rest)?;
+                match rest {
+                    [(Token::CloseParen, _), rest @ ..] => (expr, rest),
+                    [(found, span), ..] => {
+                        let err = ParseError::UncosedParen {
+                            open: *open,
+                            found: found.clone(),
+                            span: *span,
+                        };
+                        if self.errors.is_none() {
+                            return Err(err);
+                        }
+                        // Keep what was inside, as if the paren had been closed.
+                        self.report(err);
+                        (expr, rest)
+                    }
+                    [] => unreachable!("token stream ends with Eof"),
+                }
+            }
+            x => return Err(unexpected(x)),
+        })
+    }
+
+    /// Parses the body of a quantifier binding `names`.
+    fn parse_scoped<'t>(
+        &mut self,
+        names: &[(String, Option<String>)],
+        tokens: &'t [Spanned],
+        max_level: usize,
+    ) -> ParseResult<(Connective, &'t [Spanned])> {
+        let depth = self.bound.len();
+        self.bound.extend(names.iter().map(|(name, _)| name.clone()));
+        let result = self.parse_binary(tokens, max_level);
+        self.bound.truncate(depth);
+        result
+    }
+
+    /// Parses a comma separated list of terms, up to and including the closing paren.
+    fn parse_arguments<'t>(&self, tokens: &'t [Spanned]) -> ParseResult<(Vec<Term>, &'t [Spanned])> {
+        let mut args = vec![];
+        let mut rest = tokens;
+        loop {
+            let (arg, nrest) = self.parse_term(rest)?;
+            args.push(arg);
+            match nrest {
+                [(Token::Comma, _), nrest @ ..] => rest = nrest,
+                [(Token::CloseParen, _), nrest @ ..] => return Ok((args, nrest)),
+                [(x, span), ..] => return Err(ParseError::InvalidArgumentList(x.clone(), *span)),
+                [] => unreachable!("token stream ends with Eof"),
             }
         }
-        x => return Err(ParseError::UnexpectedToken(x.get(0).cloned())),
-    })
+    }
+
+    fn parse_term<'t>(&self, tokens: &'t [Spanned]) -> ParseResult<(Term, &'t [Spanned])> {
+        Ok(match tokens {
+            [(Token::Var(f), _), (Token::OpenParen, _), rest @ ..] => {
+                let (args, rest) = self.parse_arguments(rest)?;
+                (Term::Function(f.clone(), args), rest)
+            }
+            [(Token::Var(x), _), rest @ ..] if self.bound.contains(x) => (Term::Var(x.clone()), rest),
+            [(Token::Var(x), _), rest @ ..] => (Term::Const(x.clone()), rest),
+            [(x, span), ..] => return Err(ParseError::InvalidArgumentList(x.clone(), *span)),
+            [] => unreachable!("token stream ends with Eof"),
+        })
+    }
 }
 
+/// Parses `src` as a single formula with the default [`ParseOptions`], rejecting
+/// any input left over after it.
 pub fn parse(src: &str) -> ParseResult<Connective> {
-    let tokens = lex(src);
-    let (con, _) = parse_top(&tokens)?;
-    Ok(con)
+    ParseOptions::default().parse(src)
+}
+
+/// Parses the longest formula at the start of `src` with the default
+/// [`ParseOptions`], returning it together with the byte offset where parsing
+/// stopped.
+pub fn parse_prefix(src: &str) -> ParseResult<(Connective, usize)> {
+    ParseOptions::default().parse_prefix(src)
+}
+
+/// Parses an argument `A, B / C` with the default [`ParseOptions`].
+pub fn parse_argument(src: &str) -> ParseResult<Argument> {
+    ParseOptions::default().parse_argument(src)
+}
+
+/// Parses `src` with the default [`ParseOptions`], collecting every error along
+/// with a best-effort formula, see [`ParseOptions::parse_recovering`].
+pub fn parse_recovering(src: &str) -> (Connective, Vec<ParseError>) {
+    ParseOptions::default().parse_recovering(src)
+}
+
+/// Parses an argument with the default [`ParseOptions`], collecting every error
+/// along with a best-effort argument.
+pub fn parse_argument_recovering(src: &str) -> (Argument, Vec<ParseError>) {
+    ParseOptions::default().parse_argument_recovering(src)
 }
 
 #[test]
 fn out_of_bounds_index() {
     assert!(parse(".c (.a a > b)").is_ok())
 }
+
+#[test]
+fn multi_character_identifiers() {
+    assert_eq!(
+        parse("rain > wet").unwrap(),
+        Connective::Implicate(
+            box Connective::Var("rain".to_string()),
+            box Connective::Var("wet".to_string())
+        )
+    );
+    assert_eq!(
+        parse("Loves(john, mary_2)").unwrap(),
+        Connective::Predicate(
+            "Loves".to_string(),
+            vec![
+                Term::Const("john".to_string()),
+                Term::Const("mary_2".to_string())
+            ]
+        )
+    );
+}
+
+#[test]
+fn unknown_characters_are_rejected() {
+    assert!(parse("? > b").is_err())
+}
+
+#[test]
+fn unclosed_paren_points_at_the_open_paren() {
+    let src = "a & (b | c";
+    let err = parse(src).unwrap_err();
+    assert_eq!(err.span(), Span::new(10, 10));
+    assert_eq!(
+        err.render(src),
+        "1 | a & (b | c\n  |           ^\nexpected `)` to close `(` opened at column 5, found end of input"
+    );
+}
+
+#[test]
+fn error_spans_use_character_columns() {
+    let src = "¬rain ∧\n  Loves(john mary)";
+    match parse(src).unwrap_err() {
+        ParseError::InvalidArgumentList(Token::Var(x), span) => {
+            assert_eq!(x, "mary");
+            assert_eq!(span.line_col(src), (2, 14));
+        }
+        err => panic!("unexpected error {:?}", err),
+    }
+}
+
+#[test]
+fn trailing_input_is_rejected() {
+    match parse("a & b ) c").unwrap_err() {
+        ParseError::UnexpectedToken(Token::CloseParen, span) => assert_eq!(span, Span::new(6, 7)),
+        err => panic!("unexpected error {:?}", err),
+    }
+    match parse("a b").unwrap_err() {
+        ParseError::UnexpectedToken(Token::Var(x), span) => {
+            assert_eq!(x, "b");
+            assert_eq!(span, Span::new(2, 3));
+        }
+        err => panic!("unexpected error {:?}", err),
+    }
+}
+
+#[test]
+fn prefix_parsing_reports_where_it_stopped() {
+    let (con, end) = parse_prefix("a & b ) c").unwrap();
+    assert_eq!(con, parse("a & b").unwrap());
+    assert_eq!(end, 6);
+    assert_eq!(parse_prefix("a & b").unwrap().1, 5);
+}
+
+#[test]
+fn standard_precedence() {
+    let var = |x: &str| box Connective::Var(x.to_string());
+    assert_eq!(
+        parse("a | b & c").unwrap(),
+        Connective::Or(var("a"), box Connective::And(var("b"), var("c")))
+    );
+    assert_eq!(
+        parse("a & b & c").unwrap(),
+        Connective::And(box Connective::And(var("a"), var("b")), var("c"))
+    );
+    assert_eq!(
+        parse("a > b > c").unwrap(),
+        Connective::Implicate(var("a"), box Connective::Implicate(var("b"), var("c")))
+    );
+    assert_eq!(
+        parse("a > b = !a | b").unwrap(),
+        Connective::Biimplicate(
+            box Connective::Implicate(var("a"), var("b")),
+            box Connective::Or(box Connective::Not(var("a")), var("b"))
+        )
+    );
+}
+
+#[test]
+fn textbook_precedence() {
+    let options = ParseOptions {
+        precedence: PrecedenceTable::textbook(),
+        ..ParseOptions::default()
+    };
+    assert_eq!(options.parse("a & b | c").unwrap(), parse("a & (b | c)").unwrap());
+    assert_eq!(options.parse("a = b > c").unwrap(), parse("a = (b > c)").unwrap());
+}
+
+#[test]
+fn pretty_output_parses_back() {
+    use crate::PrettyOptions;
+
+    for precedence in vec![PrecedenceTable::standard(), PrecedenceTable::textbook()] {
+        let options = ParseOptions {
+            precedence: precedence.clone(),
+            ..ParseOptions::default()
+        };
+        let pretty = PrettyOptions {
+            precedence,
+            ..PrettyOptions::default()
+        };
+        for src in &[
+            "a | b & c",
+            "(a | b) & c",
+            "a & (b & c)",
+            "(a > b) > c",
+            "!(a = b) = !a & \\x .y (P(x, y) | x)",
+            "a ⊕ ((b ↑ c) ↓ d) ← e > f",
+            "(a > b) < c < (d ↑ e) ↑ f",
+        ] {
+            let con = options.parse(src).unwrap();
+            assert_eq!(options.parse(&con.pretty_with(&pretty)).unwrap(), con);
+        }
+    }
+}
+
+#[test]
+fn arguments() {
+    let argument = parse_argument("p > q, p / q").unwrap();
+    assert_eq!(argument.premises, vec![parse("p > q").unwrap(), parse("p").unwrap()]);
+    assert_eq!(argument.conclusion, parse("q").unwrap());
+
+    assert_eq!(parse_argument("p > q, p ⊢ q").unwrap(), argument);
+    assert_eq!(parse_argument("p > q, p ⊨ q").unwrap(), argument);
+    assert_eq!(parse_argument("⊢ p | !p").unwrap().premises, vec![]);
+    assert_eq!(
+        parse_argument("P(a, b) / \\x P(x, x)").unwrap().premises,
+        vec![parse("P(a, b)").unwrap()]
+    );
+}
+
+#[test]
+fn arguments_need_a_conclusion() {
+    match parse_argument("p, q").unwrap_err() {
+        ParseError::UnexpectedToken(Token::Eof, _) => {}
+        err => panic!("unexpected error {:?}", err),
+    }
+    assert!(parse_argument("p / q / r").is_err());
+}
+
+#[test]
+fn truth_constants() {
+    assert_eq!(
+        parse("p | ⊤").unwrap(),
+        Connective::Or(box Connective::Var("p".to_string()), box Connective::Top)
+    );
+    assert_eq!(
+        parse("T & F").unwrap(),
+        Connective::And(
+            box Connective::Var("T".to_string()),
+            box Connective::Var("F".to_string())
+        )
+    );
+
+    let options = ParseOptions {
+        ascii_constants: true,
+        ..ParseOptions::default()
+    };
+    assert_eq!(options.parse("T & 0").unwrap(), parse("⊤ ∧ ⊥").unwrap());
+    assert_eq!(options.parse("Tx | F_").unwrap(), parse("Tx | F_").unwrap());
+}
+
+#[test]
+fn nested_terms() {
+    let x = || Term::Var("x".to_string());
+    let a = || Term::Const("a".to_string());
+    assert_eq!(
+        parse("\\x P(f(x), g(a, h(x)), a)").unwrap(),
+        Connective::ForAll(
+            "x".to_string(),
+            None,
+            box Connective::Predicate(
+                "P".to_string(),
+                vec![
+                    Term::Function("f".to_string(), vec![x()]),
+                    Term::Function(
+                        "g".to_string(),
+                        vec![a(), Term::Function("h".to_string(), vec![x()])]
+                    ),
+                    a(),
+                ]
+            )
+        )
+    );
+    // A name bound elsewhere is a free variable outside its quantifier.
+    assert_eq!(
+        parse("P(x) & .x P(x)").unwrap(),
+        Connective::And(
+            box Connective::Predicate("P".to_string(), vec![x()]),
+            box Connective::Exists(
+                "x".to_string(),
+                None,
+                box Connective::Predicate("P".to_string(), vec![x()])
+            )
+        )
+    );
+    assert!(parse("P(f(a), )").is_err());
+    assert!(parse("P(f(a)").is_err());
+}
+
+#[test]
+fn sort_annotations() {
+    let sorted = |name: &str, sort: Option<&str>| {
+        Connective::ForAll(
+            name.to_string(),
+            sort.map(str::to_string),
+            box Connective::Predicate("P".to_string(), vec![Term::Var(name.to_string())]),
+        )
+    };
+    assert_eq!(parse("∀x:Person P(x)").unwrap(), sorted("x", Some("Person")));
+    assert_eq!(parse("\\x:Person. P(x)").unwrap(), sorted("x", Some("Person")));
+    assert_eq!(parse("∀x P(x)").unwrap(), sorted("x", None));
+    assert_eq!(
+        parse("∀x:A, y z:B. R(x, y, z)").unwrap(),
+        parse("∀x:A ∀y ∀z:B R(x, y, z)").unwrap()
+    );
+    assert!(parse("∀x:, y P(x)").is_err());
+    assert!(parse("∀x:A:B P(x)").is_err());
+}
+
+#[test]
+fn equality() {
+    let a = || Term::Const("a".to_string());
+    assert_eq!(
+        parse("f(a) == a").unwrap(),
+        Connective::Eq(Term::Function("f".to_string(), vec![a()]), a())
+    );
+    assert_eq!(parse("a ≈ b").unwrap(), parse("a == b").unwrap());
+    assert_eq!(parse("a != b").unwrap(), parse("!(a == b)").unwrap());
+    assert_eq!(parse("a ≠ b").unwrap(), parse("¬(a ≈ b)").unwrap());
+    assert_eq!(
+        parse("\\x x == a > P(x)").unwrap(),
+        parse("\\x (x == a) > P(x)").unwrap()
+    );
+    assert_eq!(
+        parse("a = b").unwrap(),
+        Connective::Biimplicate(
+            box Connective::Var("a".to_string()),
+            box Connective::Var("b".to_string())
+        )
+    );
+    assert!(parse("a == P(b) & c").is_ok());
+    assert!(parse("a == (b)").is_err());
+}
+
+#[test]
+fn additional_connectives() {
+    let var = |x: &str| box Connective::Var(x.to_string());
+    assert_eq!(
+        parse("a ⊕ b ↑ c").unwrap(),
+        Connective::Xor(var("a"), box Connective::Nand(var("b"), var("c")))
+    );
+    assert_eq!(
+        parse("(a ↓ b) ^ c").unwrap(),
+        Connective::Xor(box Connective::Nor(var("a"), var("b")), var("c"))
+    );
+    assert_eq!(
+        parse("a ← b > c").unwrap(),
+        Connective::ReverseImplicate(var("a"), box Connective::Implicate(var("b"), var("c")))
+    );
+    assert_eq!(parse("a ^ b ^ c").unwrap(), parse("(a ⊕ b) ⊕ c").unwrap());
+    assert_eq!(parse("a<b").unwrap(), parse("a ← b").unwrap());
+    assert_eq!(parse("a < b < c").unwrap(), parse("a ← (b ← c)").unwrap());
+    assert_eq!(parse("a ^ b | c").unwrap(), parse("(a ⊕ b) ∨ c").unwrap());
+}
+
+#[test]
+fn nand_and_nor_need_parentheses() {
+    let src = "a ↑ b ↑ c";
+    let err = parse(src).unwrap_err();
+    assert_eq!(err.message(src), "`↑` can't follow `↑` without parentheses");
+    assert_eq!(err.span(), Span::new(8, 11));
+    assert!(parse("a ↓ b ^ c").is_err());
+    assert!(parse("a ∧ b ↑ c").is_err());
+    assert!(parse("a ↓ b ↓ c").is_err());
+    assert_eq!(parse("(a ↑ b) ↑ c").unwrap().pretty(), "(a ↑ b) ↑ c");
+    assert_eq!(parse("a ↑ (b ↑ c)").unwrap().pretty(), "a ↑ (b ↑ c)");
+    assert_eq!(parse("a ↑ b ∨ c").unwrap(), parse("(a ↑ b) ∨ c").unwrap());
+
+    let textbook = ParseOptions {
+        precedence: PrecedenceTable::textbook(),
+        ..ParseOptions::default()
+    };
+    assert!(textbook.parse("a ↑ b ↓ c").is_err());
+    assert!(textbook.parse("a ∨ b ↓ c").is_err());
+    assert_eq!(textbook.parse("a ∧ b ∨ c").unwrap(), parse("a ∧ (b ∨ c)").unwrap());
+
+    let (con, errors) = ParseOptions::default().parse_recovering(src);
+    assert_eq!(con, parse("(a ↑ b) ↑ c").unwrap());
+    assert_eq!(errors, vec![err]);
+}
+
+#[test]
+fn quantifier_blocks() {
+    let nested = parse("∀x ∀y ∀z (P(x, y) ∧ P(y, z))").unwrap();
+    assert_eq!(parse("∀x,y,z. P(x, y) ∧ P(y, z)").unwrap(), nested);
+    assert_eq!(parse("∀x, y, z (P(x, y) ∧ P(y, z))").unwrap(), nested);
+    assert_eq!(parse("\\x y z (P(x, y) & P(y, z))").unwrap(), nested);
+    assert_eq!(
+        parse(".x,y. P(x, y)").unwrap(),
+        parse(".x .y P(x, y)").unwrap()
+    );
+    assert_eq!(parse("\\x.y P(x, y)").unwrap(), parse("\\x .y P(x, y)").unwrap());
+    assert_eq!(
+        parse("\\x P(x) > q").unwrap(),
+        parse("(\\x P(x)) > q").unwrap()
+    );
+    assert_eq!(parse("\\x(x)").unwrap(), parse("\\x x").unwrap());
+    assert_eq!(
+        parse("a & ∀x. P(x) | q").unwrap(),
+        parse("a & ∀x (P(x) | q)").unwrap()
+    );
+    assert!(parse("∀. P").is_err());
+
+    // What a quantifier binds doesn't depend on whitespace.
+    assert_eq!(parse("∀x P (x)").unwrap(), parse("∀x P(x)").unwrap());
+    assert_eq!(parse("∀x y(P(x) ∨ y)").unwrap(), parse("∀x ∀y (P(x) ∨ y)").unwrap());
+    assert_eq!(parse("\\x . y P(x, y)").unwrap(), parse("\\x.y P(x, y)").unwrap());
+    assert_eq!(parse("∀x,y .P(x, y)").unwrap(), parse("∀x,y. P(x, y)").unwrap());
+    assert_eq!(
+        parse("∀x ∃y (P(x, y))").unwrap(),
+        parse("∀x (∃y P(x, y))").unwrap()
+    );
+
+    // Parens are only arguments when they can be.
+    assert_eq!(
+        parse("∀x y (P(x, y))").unwrap(),
+        parse("∀x ∀y P(x, y)").unwrap()
+    );
+    assert_eq!(
+        parse("\\x y z (R(x, y, z))").unwrap(),
+        parse("∀x ∀y ∀z R(x, y, z)").unwrap()
+    );
+    assert_eq!(parse("∀x P(f(x))").unwrap(), parse("∀x (P(f(x)))").unwrap());
+    assert_eq!(
+        parse("∀x y (¬P(x, y))").unwrap(),
+        parse("∀x ∀y ¬P(x, y)").unwrap()
+    );
+    assert_eq!(
+        parse("∀x y ¬P(x, y)").unwrap(),
+        parse("∀x ∀y ¬P(x, y)").unwrap()
+    );
+    assert_eq!(
+        parse("\\x y !R(x, y)").unwrap(),
+        parse("∀x ∀y ¬R(x, y)").unwrap()
+    );
+    let src = "∀x y (x, y)";
+    let err = parse(src).unwrap_err();
+    assert_eq!(
+        err,
+        ParseError::AmbiguousBlock {
+            name: "y".to_string(),
+            span: Span::new(5, 13),
+        }
+    );
+    assert_eq!(
+        err.message(src),
+        "`y` could be bound by the quantifier or applied to the terms after it, \
+         write `y.` or `y(` to say which"
+    );
+}
+
+#[test]
+fn collapsed_quantifiers_parse_back() {
+    use crate::PrettyOptions;
+
+    let options = PrettyOptions {
+        collapse_quantifiers: true,
+        ..PrettyOptions::default()
+    };
+    let con = parse("∀x ∀y ∃z ∃w ∀v (P(x, y, z, w) | Q(v))").unwrap();
+    let pretty = con.pretty_with(&options);
+    assert_eq!(pretty, "∀x, y ∃z, w ∀v (P(x, y, z, w) ∨ Q(v))");
+    assert_eq!(parse(&pretty).unwrap(), con);
+    assert_eq!(con.pretty(), "∀x ∀y ∃z ∃w ∀v (P(x, y, z, w) ∨ Q(v))");
+}
+
+#[test]
+fn word_and_ascii_operators() {
+    let words = ParseOptions {
+        keywords: true,
+        ..ParseOptions::default()
+    };
+    let expected = parse("¬a ∧ b ∨ c → d ↔ ∀x ∃y P(x, y)").unwrap();
+    assert_eq!(
+        words
+            .parse("not a and b or c implies d iff forall x exists y P(x, y)")
+            .unwrap(),
+        expected
+    );
+    let digraphs = ParseOptions {
+        digraphs: true,
+        ..ParseOptions::default()
+    };
+    assert_eq!(
+        digraphs.parse("~a /\\ b \\/ c -> d <-> \\x .y P(x, y)").unwrap(),
+        expected
+    );
+    assert_eq!(
+        digraphs.parse("!a & b | c => d <=> \\x .y P(x, y)").unwrap(),
+        expected
+    );
+    assert_eq!(digraphs.parse("a <- b").unwrap(), parse("a ← b").unwrap());
+    assert_eq!(
+        words.parse("a xor (b nand c nor d)").unwrap(),
+        parse("a ⊕ (b ↑ c ↓ d)").unwrap()
+    );
+    assert_eq!(
+        digraphs.parse_argument("p -> q, p / q").unwrap(),
+        parse_argument("p > q, p / q").unwrap()
+    );
+    assert_eq!(
+        words.parse("notes and nothing").unwrap(),
+        parse("notes & nothing").unwrap()
+    );
+
+    // Without `keywords` the words are names.
+    assert_eq!(
+        parse("not ∧ P(and, or)").unwrap(),
+        Connective::And(
+            box Connective::Var("not".to_string()),
+            box Connective::Predicate(
+                "P".to_string(),
+                vec![Term::Const("and".to_string()), Term::Const("or".to_string())]
+            )
+        )
+    );
+    assert!(parse("not a").is_err());
+
+    // Without `digraphs`, `/` followed by `\` still separates the premises from
+    // the conclusion.
+    assert_eq!(
+        parse_argument("p /\\x P(x)").unwrap(),
+        parse_argument("p / ∀x P(x)").unwrap()
+    );
+    assert!(digraphs.parse("p /\\x P(x)").is_err());
+    assert!(parse("a -> b").is_err());
+}
+
+#[test]
+fn latex_input() {
+    let latex = ParseOptions {
+        latex_commands: true,
+        ..ParseOptions::default()
+    };
+    assert_eq!(
+        latex
+            .parse(r"\neg a \land b \lor c \to d \leftrightarrow \forall x \exists y P(x, y)")
+            .unwrap(),
+        parse("¬a ∧ b ∨ c → d ↔ ∀x ∃y P(x, y)").unwrap()
+    );
+    assert_eq!(latex.parse(r"\top \wedge \bot").unwrap(), parse("⊤ ∧ ⊥").unwrap());
+    assert_eq!(latex.parse(r"\x P(x)").unwrap(), parse("∀x P(x)").unwrap());
+    assert_eq!(
+        latex.parse_argument(r"p \rightarrow q, p \vdash q").unwrap(),
+        parse_argument("p > q, p / q").unwrap()
+    );
+
+    for src in &[
+        "¬(a ∧ b) ∨ c → d ↔ e",
+        "(a ⊕ b) ↑ c ↓ ⊤ ← ⊥",
+        "∀x ∃y (P(x, f(y)) → ¬Q(y))",
+        "□(p → ◇q) ∧ ¬◇p",
+        "a ≈ b ↔ f(a) ≈ f(b)",
+        "∀x (x ≠ c ∨ P(x))",
+    ] {
+        let con = parse(src).unwrap();
+        assert_eq!(latex.parse(&con.pretty_latex()).unwrap(), con);
+    }
+
+    // Without `latex_commands`, `\` is always `∀`, whatever the variable is called.
+    let forall = |x: &str, body| Connective::ForAll(x.to_string(), None, box body);
+    let top = Connective::Predicate("P".to_string(), vec![Term::Var("top".to_string())]);
+    assert_eq!(parse(r"\top P(top)").unwrap(), forall("top", top));
+    assert_eq!(
+        parse(r"\to \neg (to ∨ neg)").unwrap(),
+        forall(
+            "to",
+            forall(
+                "neg",
+                Connective::Or(
+                    box Connective::Var("to".to_string()),
+                    box Connective::Var("neg".to_string())
+                )
+            )
+        )
+    );
+    assert_eq!(parse(r"\bot.square Q(bot)").unwrap(), parse("∀bot ∃square Q(bot)").unwrap());
+}
+
+#[test]
+fn modal_operators() {
+    let p = || box Connective::Var("p".to_string());
+    assert_eq!(
+        parse("□p → ◇p").unwrap(),
+        Connective::Implicate(box Connective::Box(p()), box Connective::Diamond(p()))
+    );
+    let digraphs = ParseOptions {
+        digraphs: true,
+        ..ParseOptions::default()
+    };
+    assert_eq!(digraphs.parse("[]p -> <>p").unwrap(), parse("□p → ◇p").unwrap());
+    assert_eq!(digraphs.parse("<>a < b").unwrap(), parse("◇a ← b").unwrap());
+    let latex = ParseOptions {
+        latex_commands: true,
+        ..ParseOptions::default()
+    };
+    assert_eq!(latex.parse(r"\Box p \to \Diamond p").unwrap(), parse("□p → ◇p").unwrap());
+    assert_eq!(parse("□¬◇p").unwrap().pretty(), "□¬◇p");
+    assert_eq!(parse("□(p ∧ q)").unwrap().pretty(), "□(p ∧ q)");
+}
+
+#[test]
+fn recovering_matches_parse_on_valid_input() {
+    let options = ParseOptions::default();
+    for src in &["a & b | c", "∀x, y. P(x) → Q(y)", "¬(a ↔ ⊤)"] {
+        assert_eq!(options.parse_recovering(src), (parse(src).unwrap(), vec![]));
+    }
+    assert_eq!(
+        options.parse_argument_recovering("p > q, p / q"),
+        (parse_argument("p > q, p / q").unwrap(), vec![])
+    );
+}
+
+#[test]
+fn recovering_reports_every_error() {
+    let src = "(a & ?) | b\n| c d\n| (e";
+    let (con, errors) = ParseOptions::default().parse_recovering(src);
+
+    assert_eq!(con.pretty(), "a ∧ ? ∨ b ∨ c ∨ e");
+    let lines: Vec<_> = errors.iter().map(|err| err.span().line_col(src).0).collect();
+    assert_eq!(lines, vec![1, 2, 3]);
+    match &errors[..] {
+        [
+            ParseError::UnexpectedToken(Token::Unknown('?'), _),
+            ParseError::UnexpectedToken(Token::Var(d), _),
+            ParseError::UncosedParen { found: Token::Eof, .. },
+        ] => assert_eq!(d, "d"),
+        x => panic!("unexpected errors {:?}", x),
+    }
+}
+
+#[test]
+fn recovering_skips_to_closing_parens_and_clauses() {
+    let options = ParseOptions::default();
+
+    let (con, errors) = options.parse_recovering("P(a, ? b) & (c d e) & )");
+    assert_eq!(con.pretty(), "? ∧ c ∧ ?");
+    assert_eq!(errors.len(), 3);
+
+    let (argument, errors) = options.parse_argument_recovering("p >, q & ? / r s");
+    assert_eq!(argument.pretty(), "p → ?, q ∧ ? ⊨ r");
+    assert_eq!(errors.len(), 3);
+
+    let (argument, errors) = options.parse_argument_recovering("p, q");
+    assert_eq!(argument, parse_argument("p / q").unwrap());
+    match &errors[..] {
+        [ParseError::UnexpectedToken(Token::Eof, _)] => {}
+        x => panic!("unexpected errors {:?}", x),
+    }
+}
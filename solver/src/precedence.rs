@@ -0,0 +1,211 @@
+use crate::ast::Connective;
+use indexmap::IndexMap;
+
+/// Which way a chain of operators on the same level groups.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Assoc {
+    Left,
+    Right,
+    /// Neither: the operator can't be chained with itself or another operator on
+    /// its level without parentheses.
+    None,
+}
+
+/// The operators whose binding strength is described by a [`PrecedenceTable`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Operator {
+    /// ¬, as well as the modal □ and ◇ and the temporal X, F and G, which bind
+    /// the same way.
+    Not,
+    Quantifier,
+    And,
+    Or,
+    Implicate,
+    Biimplicate,
+    Xor,
+    Nand,
+    Nor,
+    ReverseImplicate,
+    Until,
+}
+
+impl Operator {
+    pub fn of(con: &Connective) -> Option<Operator> {
+        match con {
+            Connective::Var(_)
+            | Connective::Predicate(_, _)
+            | Connective::Eq(_, _)
+            | Connective::Top
+            | Connective::Bottom
+            | Connective::Error => None,
+            Connective::Not(_)
+            | Connective::Box(_)
+            | Connective::Diamond(_)
+            | Connective::Next(_)
+            | Connective::Eventually(_)
+            | Connective::Always(_) => Some(Operator::Not),
+            Connective::ForAll(_, _, _) | Connective::Exists(_, _, _) => Some(Operator::Quantifier),
+            Connective::And(_, _) => Some(Operator::And),
+            Connective::Or(_, _) => Some(Operator::Or),
+            Connective::Implicate(_, _) => Some(Operator::Implicate),
+            Connective::Biimplicate(_, _) => Some(Operator::Biimplicate),
+            Connective::Xor(_, _) => Some(Operator::Xor),
+            Connective::Nand(_, _) => Some(Operator::Nand),
+            Connective::Nor(_, _) => Some(Operator::Nor),
+            Connective::ReverseImplicate(_, _) => Some(Operator::ReverseImplicate),
+            Connective::Until(_, _) => Some(Operator::Until),
+        }
+    }
+
+    /// Builds the connective for a binary operator.
+    pub fn apply(self, left: Connective, right: Connective) -> Connective {
+        match self {
+            Operator::And => Connective::And(box left, box right),
+            Operator::Or => Connective::Or(box left, box right),
+            Operator::Implicate => Connective::Implicate(box left, box right),
+            Operator::Biimplicate => Connective::Biimplicate(box left, box right),
+            Operator::Xor => Connective::Xor(box left, box right),
+            Operator::Nand => Connective::Nand(box left, box right),
+            Operator::Nor => Connective::Nor(box left, box right),
+            Operator::ReverseImplicate => {
+                Connective::ReverseImplicate(box left, box right)
+            }
+            Operator::Until => Connective::Until(box left, box right),
+            Operator::Not | Operator::Quantifier => panic!("{:?} is not a binary operator", self),
+        }
+    }
+}
+
+/// Binding strength and associativity of every operator, shared by the parser and
+/// the pretty printers so that printed formulas parse back to the same tree.
+///
+/// Lower levels bind tighter and atoms sit at level 0. The operand of a prefix
+/// operator (¬ and the quantifiers) extends over everything binding at least as
+/// tight as the operator itself.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrecedenceTable {
+    base: Convention,
+    /// Operators given a level other than the convention's by
+    /// [`with`](PrecedenceTable::with).
+    overrides: IndexMap<Operator, (usize, Assoc)>,
+}
+
+/// The built-in tables a [`PrecedenceTable`] starts from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Convention {
+    Standard,
+    Textbook,
+}
+
+impl Convention {
+    fn get(self, op: Operator) -> (usize, Assoc) {
+        match self {
+            Convention::Standard => match op {
+                Operator::Not | Operator::Quantifier => (1, Assoc::Right),
+                Operator::Until => (2, Assoc::Right),
+                Operator::And => (3, Assoc::Left),
+                Operator::Nand => (3, Assoc::None),
+                Operator::Or | Operator::Xor => (4, Assoc::Left),
+                Operator::Nor => (4, Assoc::None),
+                Operator::Implicate | Operator::ReverseImplicate => (5, Assoc::Right),
+                Operator::Biimplicate => (6, Assoc::Left),
+            },
+            Convention::Textbook => match op {
+                Operator::Not | Operator::Quantifier => (1, Assoc::Right),
+                Operator::Until => (2, Assoc::Right),
+                Operator::And | Operator::Or | Operator::Xor => (3, Assoc::Right),
+                Operator::Nand | Operator::Nor => (3, Assoc::None),
+                Operator::Implicate | Operator::Biimplicate | Operator::ReverseImplicate => {
+                    (4, Assoc::Right)
+                }
+            },
+        }
+    }
+}
+
+impl PrecedenceTable {
+    /// ¬ and the quantifiers > U > ∧ > ∨ > → > ↔, with U and → grouping to the
+    /// right and the other binary operators to the left. ↑ shares a level with ∧, ⊕
+    /// and ↓ share one with ∨, and ← shares one with →. ↑ and ↓ aren't associative,
+    /// so they don't group at all.
+    pub fn standard() -> PrecedenceTable {
+        PrecedenceTable {
+            base: Convention::Standard,
+            overrides: IndexMap::new(),
+        }
+    }
+
+    /// The convention from the course textbook: ∧ and ∨ share a level, as do → and
+    /// ↔, and chains on either level group to the right, except through ↑ and ↓.
+    pub fn textbook() -> PrecedenceTable {
+        PrecedenceTable {
+            base: Convention::Textbook,
+            overrides: IndexMap::new(),
+        }
+    }
+
+    /// Overrides the level and associativity of a single operator. Levels should
+    /// be at least 1.
+    pub fn with(mut self, op: Operator, level: usize, assoc: Assoc) -> PrecedenceTable {
+        if self.base.get(op) == (level, assoc) {
+            self.overrides.swap_remove(&op);
+        } else {
+            self.overrides.insert(op, (level, assoc));
+        }
+        self
+    }
+
+    pub fn get(&self, op: Operator) -> (usize, Assoc) {
+        match self.overrides.get(&op) {
+            Some(level) => *level,
+            None => self.base.get(op),
+        }
+    }
+
+    /// The level and associativity of the outermost operator of `con`.
+    pub fn of(&self, con: &Connective) -> (usize, Assoc) {
+        match Operator::of(con) {
+            Some(op) => self.get(op),
+            None => (0, Assoc::Left),
+        }
+    }
+
+    /// The loosest levels the left and right operands of a binary operator may
+    /// have without being parenthesized.
+    pub fn operand_levels(&self, level: usize, assoc: Assoc) -> (usize, usize) {
+        match assoc {
+            Assoc::Left => (level, level.saturating_sub(1)),
+            Assoc::Right => (level.saturating_sub(1), level),
+            Assoc::None => (level.saturating_sub(1), level.saturating_sub(1)),
+        }
+    }
+}
+
+impl Default for PrecedenceTable {
+    fn default() -> PrecedenceTable {
+        PrecedenceTable::standard()
+    }
+}
+
+#[cfg(test)]
+use crate::parse::parse;
+
+#[test]
+fn overriding_levels() {
+    let table = PrecedenceTable::standard().with(Operator::Xor, 3, Assoc::Right);
+    assert_eq!(table.get(Operator::Xor), (3, Assoc::Right));
+    assert_eq!(
+        table.get(Operator::Or),
+        PrecedenceTable::standard().get(Operator::Or)
+    );
+    assert_eq!(
+        table.with(Operator::Xor, 4, Assoc::Left),
+        PrecedenceTable::standard()
+    );
+
+    assert!(parse("a ∧ b").unwrap().associative());
+    assert!(parse("a → b").unwrap().associative());
+    assert!(parse("p").unwrap().associative());
+    assert!(!parse("a ↑ b").unwrap().associative());
+    assert!(!parse("a ↓ b").unwrap().associative());
+}
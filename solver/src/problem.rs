@@ -0,0 +1,229 @@
+use crate::ast::{Argument, Connective};
+use crate::parse::{is_ident_char, ParseError, ParseOptions, Span};
+
+/// A set of premises together with the goals that should follow from them.
+/// Premises and goals may be given a name in the problem file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Problem {
+    pub name: Option<String>,
+    pub premises: Vec<(Option<String>, Connective)>,
+    pub goals: Vec<(Option<String>, Connective)>,
+}
+
+impl Problem {
+    /// One argument per goal, each concluding the goal from every premise.
+    pub fn arguments(&self) -> Vec<Argument> {
+        let premises: Vec<_> = self
+            .premises
+            .iter()
+            .map(|(_, premise)| premise.clone())
+            .collect();
+        self.goals
+            .iter()
+            .map(|(_, goal)| Argument {
+                premises: premises.clone(),
+                conclusion: goal.clone(),
+            })
+            .collect()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.name.is_none() && self.premises.is_empty() && self.goals.is_empty()
+    }
+}
+
+/// Splits `name: rest` at the first `:`, where the name may be left out.
+fn named(text: &str, offset: usize) -> Result<(Option<String>, &str, usize), ParseError> {
+    let colon = text.find(':').ok_or(ParseError::InvalidStatement {
+        expected: "`:`",
+        span: Span::new(offset + text.len(), offset + text.len()),
+    })?;
+    let name = text[..colon].trim();
+    if !name.chars().all(is_ident_char) {
+        let start = offset + text.find(name).unwrap_or(0);
+        return Err(ParseError::InvalidStatement {
+            expected: "a name",
+            span: Span::new(start, start + name.len()),
+        });
+    }
+    let name = if name.is_empty() {
+        None
+    } else {
+        Some(name.to_string())
+    };
+    Ok((name, &text[colon + 1..], offset + colon + 1))
+}
+
+impl ParseOptions {
+    /// Parses a problem file, where every non-empty line is one of
+    ///
+    /// - `problem name`, starting a new problem,
+    /// - `premise name: φ`, adding a premise to the current problem,
+    /// - `goal name: ψ`, adding a goal that should follow from the premises,
+    /// - `let X := φ`, after which `X` stands for `φ` in the rest of the file, or
+    ///   `let X(a, b) := φ`, after which `X(s, t)` stands for `φ` with `s` and `t`
+    ///   in place of `a` and `b` (see [`ParseOptions::define`]).
+    ///
+    /// The names of premises and goals may be left out, as in `goal: ψ`, and `#`
+    /// starts a comment running to the end of the line. Statements before the first
+    /// `problem` line make up a problem without a name.
+    pub fn parse_problems(&self, src: &str) -> Result<Vec<Problem>, ParseError> {
+        let mut problems = vec![];
+        let mut problem = Problem::default();
+        let mut options = self.clone();
+
+        let mut line_start = 0;
+        for line in src.split('\n') {
+            let mut offset = line_start;
+            line_start += line.len() + 1;
+
+            let line = line.split('#').next().unwrap_or("");
+            offset += line.len() - line.trim_start().len();
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let keyword_len = line.find(|c| !is_ident_char(c)).unwrap_or(line.len());
+            let rest = &line[keyword_len..];
+            let rest_offset = offset + keyword_len;
+            let formula = |text: &str, at: usize| options.parse(text).map_err(|err| err.offset(at));
+
+            match &line[..keyword_len] {
+                "problem" => {
+                    if !problem.is_empty() {
+                        problems.push(problem);
+                    }
+                    let name = rest.trim();
+                    problem = Problem {
+                        name: if name.is_empty() {
+                            None
+                        } else {
+                            Some(name.to_string())
+                        },
+                        ..Problem::default()
+                    };
+                }
+                "premise" => {
+                    let (name, text, at) = named(rest, rest_offset)?;
+                    problem.premises.push((name, formula(text, at)?));
+                }
+                "goal" => {
+                    let (name, text, at) = named(rest, rest_offset)?;
+                    problem.goals.push((name, formula(text, at)?));
+                }
+                "let" => options
+                    .define(rest)
+                    .map_err(|err| err.offset(rest_offset))?,
+                _ => {
+                    return Err(ParseError::InvalidStatement {
+                        expected: "`problem`, `premise`, `goal` or `let`",
+                        span: Span::new(offset, offset + keyword_len.max(1)),
+                    })
+                }
+            }
+        }
+
+        if !problem.is_empty() {
+            problems.push(problem);
+        }
+        Ok(problems)
+    }
+}
+
+/// Parses a problem file with the default [`ParseOptions`], see
+/// [`ParseOptions::parse_problems`].
+pub fn parse_problems(src: &str) -> Result<Vec<Problem>, ParseError> {
+    ParseOptions::default().parse_problems(src)
+}
+
+#[cfg(test)]
+use crate::parse::{parse, parse_argument};
+
+#[test]
+fn problems_with_definitions() {
+    let src = "
+# Everything before the first `problem` line belongs to an unnamed problem.
+let Rule := p > q   # shared by every later problem
+premise: Rule
+goal: p > q
+
+problem modus_ponens
+premise rule: Rule
+premise fact: p
+goal: q
+goal weaker: q | r
+";
+    let problems = parse_problems(src).unwrap();
+    assert_eq!(problems.len(), 2);
+
+    assert_eq!(problems[0].name, None);
+    assert_eq!(problems[0].premises, vec![(None, parse("p > q").unwrap())]);
+
+    let problem = &problems[1];
+    assert_eq!(problem.name, Some("modus_ponens".to_string()));
+    assert_eq!(
+        problem.premises,
+        vec![
+            (Some("rule".to_string()), parse("p > q").unwrap()),
+            (Some("fact".to_string()), parse("p").unwrap()),
+        ]
+    );
+    assert_eq!(
+        problem.arguments(),
+        vec![
+            parse_argument("p > q, p / q").unwrap(),
+            parse_argument("p > q, p / q | r").unwrap(),
+        ]
+    );
+    for argument in problem.arguments() {
+        assert!(argument.tableau().is_closed());
+    }
+}
+
+#[test]
+fn definitions_expand_inside_later_definitions() {
+    let src = "let A := p & q\nlet B := A | r\ngoal: B > A";
+    let problems = parse_problems(src).unwrap();
+    assert_eq!(
+        problems[0].goals[0].1,
+        parse("(p & q | r) > p & q").unwrap()
+    );
+}
+
+#[test]
+fn parameterised_definitions() {
+    let src = "
+let Trans(R) := \\x\\y\\z (R(x,y) & R(y,z) > R(x,z))
+premise: Trans(Less)
+premise: Less(a, b) & Less(b, c)
+goal: Less(a, c)
+";
+    let problems = parse_problems(src).unwrap();
+    assert_eq!(
+        problems[0].premises[0].1,
+        parse("∀x ∀y ∀z (Less(x, y) ∧ Less(y, z) → Less(x, z))").unwrap()
+    );
+    assert!(problems[0].arguments()[0].tableau().is_closed());
+
+    let err = parse_problems("premise: p\nlet Trans(R := p").unwrap_err();
+    assert_eq!(err.span().line_col("premise: p\nlet Trans(R := p"), (2, 13));
+}
+
+#[test]
+fn problem_errors_point_into_the_file() {
+    let src = "problem broken\npremise a: p &\n";
+    let err = parse_problems(src).unwrap_err();
+    assert_eq!(err.span().line_col(src), (2, 15));
+
+    let src = "premise a: p\nconclusion: q";
+    let err = parse_problems(src).unwrap_err();
+    assert_eq!(err.span().line_col(src), (2, 1));
+    assert_eq!(
+        err.message(src),
+        "expected `problem`, `premise`, `goal` or `let`"
+    );
+
+    assert!(parse_problems("let := p").is_err());
+    assert!(parse_problems("goal q").is_err());
+}
@@ -0,0 +1,649 @@
+use crate::ast::{Argument, Connective, Term};
+use crate::sort::{Signature, SortError};
+use crate::visit::{walk_connective, walk_connective_mut, MutVisitor, Visitor};
+use indexmap::{IndexMap, IndexSet};
+
+/// The sort of the terms a signature says nothing about.
+const SORT: &str = "U";
+
+/// Words of SMT-LIB that a name must not be mistaken for.
+const RESERVED: [&str; 19] = [
+    "true", "false", "not", "and", "or", "xor", "=>", "=", "distinct", "ite", "let", "forall",
+    "exists", "match", "par", "as", "_", "!", "assert",
+];
+
+/// A name as an SMT-LIB symbol, quoted with `|` unless it is a plain ASCII word.
+/// A quoted symbol can't contain `|` or `\`, so those are written `%7C` and `%5C`,
+/// and `%` itself `%25` to keep different names apart.
+fn symbol(name: &str) -> String {
+    let simple = name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+        && !name.is_empty()
+        && !RESERVED.contains(&name);
+    if simple {
+        name.to_string()
+    } else {
+        let escaped = name
+            .replace('%', "%25")
+            .replace('|', "%7C")
+            .replace('\\', "%5C");
+        format!("|{}|", escaped)
+    }
+}
+
+/// The kinds of names a formula uses, which SMT-LIB keeps in a single namespace.
+#[derive(Clone, Copy)]
+enum Namespace {
+    Proposition,
+    Predicate,
+    Function,
+    Variable,
+}
+
+impl Namespace {
+    /// Appended to a name used in more than one namespace. Parsed names never
+    /// contain a `.`, so the result can't be another name of the formula.
+    fn suffix(self) -> &'static str {
+        match self {
+            Namespace::Proposition => ".prop",
+            Namespace::Predicate => ".pred",
+            Namespace::Function => ".fun",
+            Namespace::Variable => ".var",
+        }
+    }
+}
+
+/// Variables standing for the sorts of terms, merged as terms turn out to share a
+/// sort, each set known to be of a sort or not yet.
+#[derive(Default)]
+struct SortVars {
+    parent: Vec<usize>,
+    sorts: Vec<Option<String>>,
+}
+
+impl SortVars {
+    fn fresh(&mut self, sort: Option<&str>) -> usize {
+        self.parent.push(self.parent.len());
+        self.sorts.push(sort.map(str::to_string));
+        self.parent.len() - 1
+    }
+
+    fn find(&self, mut var: usize) -> usize {
+        while self.parent[var] != var {
+            var = self.parent[var];
+        }
+        var
+    }
+
+    fn get(&self, var: usize) -> Option<&str> {
+        self.sorts[self.find(var)].as_deref()
+    }
+
+    /// Merges the sets of `a` and `b`, or returns their sorts if both are known and
+    /// differ.
+    fn unify(&mut self, a: usize, b: usize) -> Result<(), (String, String)> {
+        let (a, b) = (self.find(a), self.find(b));
+        match (&self.sorts[a], &self.sorts[b]) {
+            _ if a == b => return Ok(()),
+            (Some(x), Some(y)) if x != y => return Err((x.clone(), y.clone())),
+            _ => {}
+        }
+        self.parent[a] = b;
+        if self.sorts[b].is_none() {
+            self.sorts[b] = self.sorts[a].take();
+        }
+        Ok(())
+    }
+}
+
+/// Writes the sort found for each binder into its annotation, taking the binders
+/// in the order [`Script`] visited them.
+struct Annotate<'s> {
+    vars: &'s SortVars,
+    binders: std::slice::Iter<'s, usize>,
+}
+
+impl MutVisitor for Annotate<'_> {
+    fn visit_connective_mut(&mut self, con: &mut Connective) {
+        match con {
+            Connective::Box(_)
+            | Connective::Diamond(_)
+            | Connective::Next(_)
+            | Connective::Eventually(_)
+            | Connective::Always(_)
+            | Connective::Until(_, _) => {}
+            Connective::ForAll(_, sort, _) | Connective::Exists(_, sort, _) => {
+                let var = *self.binders.next().expect("binder visited by the script");
+                *sort = self.vars.get(var).map(str::to_string);
+                walk_connective_mut(self, con);
+            }
+            _ => walk_connective_mut(self, con),
+        }
+    }
+}
+
+/// The symbols a script declares, with the sort variables of the arguments of
+/// predicates and of the arguments and results of functions, and the sorts it has
+/// used so far. Visiting a formula declares the symbols in it and infers the sorts
+/// of its terms from the signature, the binders' annotations and each other.
+struct Script<'a> {
+    signature: &'a Signature,
+    propositions: IndexSet<String>,
+    predicates: IndexMap<String, Vec<usize>>,
+    functions: IndexMap<String, (Vec<usize>, usize)>,
+    variables: IndexSet<String>,
+    sorts: IndexSet<String>,
+    vars: SortVars,
+    /// The sort variables of the binders enclosing the visited formula.
+    bound: Vec<(String, usize)>,
+    /// The sort variable of every binder, in the order they were visited.
+    binders: Vec<usize>,
+    errors: Vec<SortError>,
+}
+
+impl<'a> Script<'a> {
+    fn new(signature: &'a Signature) -> Script<'a> {
+        Script {
+            signature,
+            propositions: IndexSet::new(),
+            predicates: IndexMap::new(),
+            functions: IndexMap::new(),
+            variables: IndexSet::new(),
+            sorts: IndexSet::new(),
+            vars: SortVars::default(),
+            bound: vec![],
+            binders: vec![],
+            errors: vec![],
+        }
+    }
+
+    /// Declares the symbols of `formulas`, returning copies of them with every
+    /// binder annotated with the sort of the terms it stands for, or the places
+    /// where terms of different sorts are used alike.
+    fn declare(&mut self, formulas: &[&Connective]) -> Result<Vec<Connective>, Vec<SortError>> {
+        for con in formulas {
+            self.visit_connective(con);
+        }
+        if !self.errors.is_empty() {
+            return Err(self.errors.clone());
+        }
+        let mut annotate = Annotate {
+            vars: &self.vars,
+            binders: self.binders.iter(),
+        };
+        Ok(formulas
+            .iter()
+            .map(|con| {
+                let mut con = (*con).clone();
+                annotate.visit_connective_mut(&mut con);
+                con
+            })
+            .collect())
+    }
+
+    /// The sort variables of the arguments of a predicate, of the sorts the
+    /// signature declares if it does so for `arity` arguments.
+    fn predicate(&mut self, p: &str, arity: usize) -> Vec<usize> {
+        if let Some(args) = self.predicates.get(p) {
+            return args.clone();
+        }
+        let signature = self.signature;
+        let args: Vec<_> = match signature.predicate_sorts(p) {
+            Some(sorts) if sorts.len() == arity => sorts
+                .iter()
+                .map(|sort| self.vars.fresh(Some(sort)))
+                .collect(),
+            _ => (0..arity).map(|_| self.vars.fresh(None)).collect(),
+        };
+        self.predicates.insert(p.to_string(), args.clone());
+        args
+    }
+
+    /// The sort variables of the arguments and result of a function, like
+    /// [`predicate`](Script::predicate).
+    fn function(&mut self, f: &str, arity: usize) -> (Vec<usize>, usize) {
+        if let Some(slots) = self.functions.get(f) {
+            return slots.clone();
+        }
+        let signature = self.signature;
+        let slots = match signature.function_sorts(f) {
+            Some((sorts, result)) if sorts.len() == arity => {
+                let args = sorts
+                    .iter()
+                    .map(|sort| self.vars.fresh(Some(sort)))
+                    .collect();
+                (args, self.vars.fresh(Some(result)))
+            }
+            _ => {
+                let args = (0..arity).map(|_| self.vars.fresh(None)).collect();
+                (args, self.vars.fresh(None))
+            }
+        };
+        self.functions.insert(f.to_string(), slots.clone());
+        slots
+    }
+
+    /// Gives each argument the sort of its place, recording where it can't.
+    fn arguments(&mut self, symbol: &str, places: Vec<usize>, args: &[Term]) {
+        let found: Vec<_> = args.iter().map(|arg| self.term_sort(arg)).collect();
+        if places.len() != args.len() {
+            self.errors.push(SortError::Arity {
+                symbol: symbol.to_string(),
+                expected: places.len(),
+                found: args.len(),
+            });
+            return;
+        }
+        for ((arg, found), place) in args.iter().zip(found).zip(places) {
+            if let Err((expected, found)) = self.vars.unify(place, found) {
+                self.errors.push(SortError::Mismatch {
+                    symbol: symbol.to_string(),
+                    term: arg.clone(),
+                    expected,
+                    found,
+                });
+            }
+        }
+    }
+
+    /// The sort variable of a term.
+    fn term_sort(&mut self, term: &Term) -> usize {
+        match term {
+            Term::Var(x) => match self.bound.iter().rev().find(|(y, _)| y == x) {
+                Some((_, var)) => *var,
+                None => self.vars.fresh(None),
+            },
+            Term::Const(f) => self.term_sort(&Term::Function(f.clone(), vec![])),
+            Term::Function(f, args) => {
+                let (places, result) = self.function(f, args.len());
+                self.arguments(f, places, args);
+                result
+            }
+        }
+    }
+
+    /// The symbol for `name`, suffixed with its namespace if another namespace
+    /// uses the same name.
+    fn name(&self, namespace: Namespace, name: &str) -> String {
+        let uses = [
+            self.propositions.contains(name),
+            self.predicates.contains_key(name),
+            self.functions.contains_key(name),
+            self.variables.contains(name),
+        ];
+        if uses.iter().filter(|used| **used).count() > 1 {
+            symbol(&format!("{}{}", name, namespace.suffix()))
+        } else {
+            symbol(name)
+        }
+    }
+
+    /// The symbol for a sort, [`SORT`] if it isn't known.
+    fn sort(&mut self, sort: Option<&str>) -> String {
+        let sort = sort.unwrap_or(SORT);
+        self.sorts.insert(sort.to_string());
+        symbol(sort)
+    }
+
+    fn term(&self, term: &Term) -> String {
+        match term {
+            Term::Var(x) => self.name(Namespace::Variable, x),
+            Term::Const(c) => self.name(Namespace::Function, c),
+            Term::Function(f, args) => format!(
+                "({} {})",
+                self.name(Namespace::Function, f),
+                self.terms(args)
+            ),
+        }
+    }
+
+    fn terms(&self, terms: &[Term]) -> String {
+        terms
+            .iter()
+            .map(|term| self.term(term))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    fn binary(&mut self, op: &str, a: &Connective, b: &Connective) -> String {
+        format!("({} {} {})", op, self.formula(a), self.formula(b))
+    }
+
+    fn formula(&mut self, con: &Connective) -> String {
+        match con {
+            Connective::Var(p) => self.name(Namespace::Proposition, p),
+            Connective::Error => self.name(Namespace::Proposition, "?"),
+            Connective::Box(_)
+            | Connective::Diamond(_)
+            | Connective::Next(_)
+            | Connective::Eventually(_)
+            | Connective::Always(_)
+            | Connective::Until(_, _) => self.name(Namespace::Proposition, &con.pretty()),
+            Connective::Top => "true".to_string(),
+            Connective::Bottom => "false".to_string(),
+            Connective::Predicate(p, args) => format!(
+                "({} {})",
+                self.name(Namespace::Predicate, p),
+                self.terms(args)
+            ),
+            Connective::Eq(a, b) => format!("(= {} {})", self.term(a), self.term(b)),
+            Connective::Not(x) => format!("(not {})", self.formula(x)),
+            Connective::And(a, b) => self.binary("and", a, b),
+            Connective::Or(a, b) => self.binary("or", a, b),
+            Connective::Implicate(a, b) => self.binary("=>", a, b),
+            Connective::Biimplicate(a, b) => self.binary("=", a, b),
+            Connective::Xor(a, b) => self.binary("xor", a, b),
+            Connective::Nand(a, b) => format!("(not {})", self.binary("and", a, b)),
+            Connective::Nor(a, b) => format!("(not {})", self.binary("or", a, b)),
+            Connective::ReverseImplicate(a, b) => self.binary("=>", b, a),
+            Connective::ForAll(_, _, _) | Connective::Exists(_, _, _) => {
+                let q = if let Connective::ForAll(_, _, _) = con {
+                    "forall"
+                } else {
+                    "exists"
+                };
+                let (binders, body) = con.quantifier_block(true);
+                // An inner binder hides an outer one of the same name, which SMT-LIB
+                // doesn't allow within one block, so only the last is kept.
+                let bindings: Vec<_> = binders
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, (x, _))| binders[i + 1..].iter().all(|(y, _)| x != y))
+                    .map(|(_, (x, sort))| {
+                        format!(
+                            "({} {})",
+                            self.name(Namespace::Variable, x),
+                            self.sort(*sort)
+                        )
+                    })
+                    .collect();
+                format!("({} ({}) {})", q, bindings.join(" "), self.formula(body))
+            }
+        }
+    }
+
+    /// The sort and symbol declarations followed by the assertions, the sorts being
+    /// those of the declared symbols and of any binders in the assertions.
+    fn render(&mut self, assertions: &[String]) -> String {
+        let vars = &self.vars;
+        let resolve = |places: &[usize]| -> Vec<Option<String>> {
+            places
+                .iter()
+                .map(|place| vars.get(*place).map(str::to_string))
+                .collect()
+        };
+        let functions: Vec<_> = self
+            .functions
+            .iter()
+            .map(|(f, (args, result))| (f.clone(), resolve(args), resolve(&[*result]).remove(0)))
+            .collect();
+        let predicates: Vec<_> = self
+            .predicates
+            .iter()
+            .map(|(p, args)| (p.clone(), resolve(args)))
+            .collect();
+
+        let mut declarations = String::new();
+        for p in &self.propositions {
+            declarations += &format!(
+                "(declare-const {} Bool)\n",
+                self.name(Namespace::Proposition, p)
+            );
+        }
+        for (f, args, result) in functions {
+            let args: Vec<_> = args.iter().map(|sort| self.sort(sort.as_deref())).collect();
+            let name = self.name(Namespace::Function, &f);
+            let result = self.sort(result.as_deref());
+            declarations += &match args.len() {
+                0 => format!("(declare-const {} {})\n", name, result),
+                _ => format!("(declare-fun {} ({}) {})\n", name, args.join(" "), result),
+            };
+        }
+        for (p, args) in predicates {
+            let args: Vec<_> = args.iter().map(|sort| self.sort(sort.as_deref())).collect();
+            declarations += &format!(
+                "(declare-fun {} ({}) Bool)\n",
+                self.name(Namespace::Predicate, &p),
+                args.join(" ")
+            );
+        }
+
+        let mut out = String::new();
+        for sort in &self.sorts {
+            out += &format!("(declare-sort {} 0)\n", symbol(sort));
+        }
+        out += &declarations;
+        assertions.iter().for_each(|assertion| out += assertion);
+        out += "(check-sat)\n";
+        out
+    }
+}
+
+impl Visitor for Script<'_> {
+    fn visit_connective(&mut self, con: &Connective) {
+        match con {
+            Connective::Var(p) => {
+                self.propositions.insert(p.clone());
+            }
+            Connective::Error => {
+                self.propositions.insert("?".to_string());
+            }
+            Connective::Box(_)
+            | Connective::Diamond(_)
+            | Connective::Next(_)
+            | Connective::Eventually(_)
+            | Connective::Always(_)
+            | Connective::Until(_, _) => {
+                self.propositions.insert(con.pretty());
+            }
+            Connective::Predicate(p, args) => {
+                let places = self.predicate(p, args.len());
+                self.arguments(p, places, args);
+            }
+            Connective::Eq(a, b) => {
+                let (a_sort, b_sort) = (self.term_sort(a), self.term_sort(b));
+                if let Err((expected, found)) = self.vars.unify(a_sort, b_sort) {
+                    self.errors.push(SortError::Mismatch {
+                        symbol: "≈".to_string(),
+                        term: b.clone(),
+                        expected,
+                        found,
+                    });
+                }
+            }
+            Connective::ForAll(x, sort, body) | Connective::Exists(x, sort, body) => {
+                let var = self.vars.fresh(sort.as_deref());
+                self.variables.insert(x.clone());
+                self.binders.push(var);
+                self.bound.push((x.clone(), var));
+                self.visit_connective(body);
+                self.bound.pop();
+            }
+            _ => walk_connective(self, con),
+        }
+    }
+}
+
+impl Connective {
+    /// The formula as an SMT-LIB 2 script checking whether it is satisfiable. The
+    /// propositions, predicates, constants and functions it uses are declared first.
+    /// Terms take the sort of the binders they are used alike with, and are of a
+    /// single sort `U` otherwise. Modal and temporal formulas are declared as
+    /// propositions of their own.
+    ///
+    /// Fails with the places where terms of different sorts are used alike, such
+    /// as `∀x:Pet ∀y:Person (P(x) ∧ P(y))`, since SMT-LIB would reject the script.
+    pub fn to_smtlib(&self) -> Result<String, Vec<SortError>> {
+        self.to_smtlib_with(&Signature::default())
+    }
+
+    /// Like [`to_smtlib`](Connective::to_smtlib), but with the sorts the signature
+    /// declares, which the sorts of other terms and unannotated binders follow
+    /// from.
+    pub fn to_smtlib_with(&self, signature: &Signature) -> Result<String, Vec<SortError>> {
+        let mut script = Script::new(signature);
+        let con = script.declare(&[self])?.remove(0);
+        let assertion = format!("(assert {})\n", script.formula(&con));
+        Ok(script.render(&[assertion]))
+    }
+}
+
+impl Argument {
+    /// The argument as an SMT-LIB 2 script asserting the premises and the negated
+    /// conclusion, so the solver answers `unsat` exactly when the argument is valid.
+    pub fn to_smtlib(&self) -> Result<String, Vec<SortError>> {
+        self.to_smtlib_with(&Signature::default())
+    }
+
+    /// Like [`to_smtlib`](Argument::to_smtlib), with the sorts of a signature, see
+    /// [`Connective::to_smtlib_with`].
+    pub fn to_smtlib_with(&self, signature: &Signature) -> Result<String, Vec<SortError>> {
+        let mut script = Script::new(signature);
+        let formulas: Vec<_> = self.premises.iter().chain(Some(&self.conclusion)).collect();
+        let mut formulas = script.declare(&formulas)?;
+        let conclusion = formulas.pop().expect("the conclusion is declared last");
+
+        let mut assertions: Vec<_> = formulas
+            .iter()
+            .map(|premise| format!("(assert {})\n", script.formula(premise)))
+            .collect();
+        assertions.push(format!("(assert (not {}))\n", script.formula(&conclusion)));
+        Ok(script.render(&assertions))
+    }
+}
+
+#[cfg(test)]
+use crate::parse::{parse, parse_argument};
+
+#[test]
+fn propositional_smtlib() {
+    assert_eq!(
+        parse("(p ∧ q) → ¬(r ↑ p) ∨ (distinct ⊕ ⊤)")
+            .unwrap()
+            .to_smtlib()
+            .unwrap(),
+        "(declare-const p Bool)\n\
+         (declare-const q Bool)\n\
+         (declare-const r Bool)\n\
+         (declare-const |distinct| Bool)\n\
+         (assert (=> (and p q) (or (not (not (and r p))) (xor |distinct| true))))\n\
+         (check-sat)\n"
+    );
+}
+
+#[test]
+fn first_order_smtlib() {
+    assert_eq!(
+        parse_argument("∀x,y. Loves(x, f(y)) ← x ≈ bob / Loves(bob, f(ann))")
+            .unwrap()
+            .to_smtlib()
+            .unwrap(),
+        "(declare-sort U 0)\n\
+         (declare-fun f (U) U)\n\
+         (declare-const bob U)\n\
+         (declare-const ann U)\n\
+         (declare-fun Loves (U U) Bool)\n\
+         (assert (forall ((x U) (y U)) (=> (= x bob) (Loves x (f y)))))\n\
+         (assert (not (Loves bob (f ann))))\n\
+         (check-sat)\n"
+    );
+}
+
+#[test]
+fn namespaces_and_binders_stay_apart() {
+    // Parsed separately, so that the `p` in `P(p)` is a constant.
+    let con = Connective::And(
+        box parse("p ∧ P(p)").unwrap(),
+        box parse("∀p ∀q ∀p Q(p, q)").unwrap(),
+    );
+    assert_eq!(
+        con.to_smtlib().unwrap(),
+        "(declare-sort U 0)\n\
+         (declare-const |p.prop| Bool)\n\
+         (declare-const |p.fun| U)\n\
+         (declare-fun P (U) Bool)\n\
+         (declare-fun Q (U U) Bool)\n\
+         (assert (and (and |p.prop| (P |p.fun|)) (forall ((q U) (|p.var| U)) (Q |p.var| q))))\n\
+         (check-sat)\n"
+    );
+}
+
+#[test]
+fn sorts_from_a_signature() {
+    let signature = Signature::new()
+        .predicate("Owns", &["Person", "Pet"])
+        .function("owner", &["Pet"], "Person")
+        .constant("rex", "Pet");
+    assert_eq!(
+        parse("∀x Owns(owner(x), x) ∧ ∃y:Person ∃z (Owns(y, rex) ∧ z ≈ ann)")
+            .unwrap()
+            .to_smtlib_with(&signature)
+            .unwrap(),
+        "(declare-sort Pet 0)\n\
+         (declare-sort Person 0)\n\
+         (declare-sort U 0)\n\
+         (declare-fun owner (Pet) Person)\n\
+         (declare-const rex Pet)\n\
+         (declare-const ann U)\n\
+         (declare-fun Owns (Person Pet) Bool)\n\
+         (assert (and (forall ((x Pet)) (Owns (owner x) x)) \
+         (exists ((y Person) (z U)) (and (Owns y rex) (= z ann)))))\n\
+         (check-sat)\n"
+    );
+}
+
+#[test]
+fn declarations_follow_the_binders() {
+    assert_eq!(
+        parse("∀x:Pet (Barks(x) ∨ x ≈ rex) ∧ ∃y y ≈ rex")
+            .unwrap()
+            .to_smtlib()
+            .unwrap(),
+        "(declare-sort Pet 0)\n\
+         (declare-const rex Pet)\n\
+         (declare-fun Barks (Pet) Bool)\n\
+         (assert (and (forall ((x Pet)) (or (Barks x) (= x rex))) (exists ((y Pet)) (= y rex))))\n\
+         (check-sat)\n"
+    );
+
+    let signature = Signature::new().constant("rex", "Pet");
+    assert_eq!(
+        parse("∃y Likes(y, rex) ∧ ∀z Likes(z, z)")
+            .unwrap()
+            .to_smtlib_with(&signature)
+            .unwrap(),
+        "(declare-sort Pet 0)\n\
+         (declare-const rex Pet)\n\
+         (declare-fun Likes (Pet Pet) Bool)\n\
+         (assert (and (exists ((y Pet)) (Likes y rex)) (forall ((z Pet)) (Likes z z))))\n\
+         (check-sat)\n"
+    );
+
+    assert_eq!(
+        parse("∀x:Pet Barks(x) ∧ ∀y:Person Barks(y)")
+            .unwrap()
+            .to_smtlib(),
+        Err(vec![SortError::Mismatch {
+            symbol: "Barks".to_string(),
+            term: Term::Var("y".to_string()),
+            expected: "Pet".to_string(),
+            found: "Person".to_string(),
+        }])
+    );
+    assert_eq!(
+        parse("∀x:Pet x ≈ rex")
+            .unwrap()
+            .to_smtlib_with(&Signature::new().constant("rex", "Person"))
+            .unwrap_err()
+            .len(),
+        1
+    );
+}
+
+#[test]
+fn quoted_symbols_stay_distinct() {
+    assert_eq!(symbol("a|b"), "|a%7Cb|");
+    assert_eq!(symbol("a\\b"), "|a%5Cb|");
+    assert_eq!(symbol("a%7Cb"), "|a%257Cb|");
+    assert_ne!(symbol("a|b"), symbol("ab"));
+}
@@ -0,0 +1,297 @@
+use crate::ast::{Connective, Term};
+use crate::visit::{walk_connective, Visitor};
+use indexmap::IndexMap;
+
+/// The sorts of the predicates, functions and constants of a many-sorted language,
+/// built up like
+///
+/// ```
+/// # use solver::Signature;
+/// let signature = Signature::new()
+///     .predicate("Owns", &["Person", "Pet"])
+///     .function("owner", &["Pet"], "Person")
+///     .constant("rex", "Pet");
+/// ```
+///
+/// Symbols the signature doesn't mention may be used with terms of any sort.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Signature {
+    predicates: IndexMap<String, Vec<String>>,
+    functions: IndexMap<String, (Vec<String>, String)>,
+}
+
+/// A term used where the signature says it can't be.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SortError {
+    /// `term` of sort `found` is an argument of `symbol` where `expected` belongs,
+    /// or one side of an equality whose other side is of sort `expected`.
+    Mismatch {
+        symbol: String,
+        term: Term,
+        expected: String,
+        found: String,
+    },
+    /// `symbol` is applied to `found` arguments but declared with `expected`.
+    Arity {
+        symbol: String,
+        expected: usize,
+        found: usize,
+    },
+}
+
+impl SortError {
+    pub fn message(&self) -> String {
+        match self {
+            SortError::Mismatch {
+                symbol,
+                term,
+                expected,
+                found,
+            } => format!(
+                "`{}` is of sort {} but {} expects {}",
+                term.pretty(),
+                found,
+                symbol,
+                expected
+            ),
+            SortError::Arity {
+                symbol,
+                expected,
+                found,
+            } => format!(
+                "{} takes {} arguments but is given {}",
+                symbol, expected, found
+            ),
+        }
+    }
+}
+
+/// Whether a term of sort `a` may stand where sort `b` is expected, which only
+/// fails when both are known and differ.
+pub(crate) fn compatible(a: Option<&str>, b: Option<&str>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => a == b,
+        _ => true,
+    }
+}
+
+impl Signature {
+    pub fn new() -> Signature {
+        Signature::default()
+    }
+
+    /// Declares the sorts of the arguments of a predicate.
+    pub fn predicate(mut self, name: &str, args: &[&str]) -> Signature {
+        let args = args.iter().map(|sort| sort.to_string()).collect();
+        self.predicates.insert(name.to_string(), args);
+        self
+    }
+
+    /// Declares the sorts of the arguments and the result of a function.
+    pub fn function(mut self, name: &str, args: &[&str], result: &str) -> Signature {
+        let args = args.iter().map(|sort| sort.to_string()).collect();
+        self.functions
+            .insert(name.to_string(), (args, result.to_string()));
+        self
+    }
+
+    /// Declares the sort of a constant.
+    pub fn constant(self, name: &str, sort: &str) -> Signature {
+        self.function(name, &[], sort)
+    }
+
+    /// The sort of a ground term, if the signature declares it.
+    pub fn sort_of(&self, term: &Term) -> Option<&str> {
+        match term {
+            Term::Var(_) => None,
+            Term::Const(name) | Term::Function(name, _) => {
+                self.functions.get(name).map(|(_, result)| result.as_str())
+            }
+        }
+    }
+
+    /// The sorts of the arguments of a predicate, if the signature declares it.
+    pub(crate) fn predicate_sorts(&self, name: &str) -> Option<&[String]> {
+        self.predicates.get(name).map(|sorts| &sorts[..])
+    }
+
+    /// The sorts of the arguments and the result of a function or constant, if the
+    /// signature declares it.
+    pub(crate) fn function_sorts(&self, name: &str) -> Option<(&[String], &str)> {
+        self.functions
+            .get(name)
+            .map(|(args, result)| (&args[..], result.as_str()))
+    }
+
+    /// Every place in `con` where a term is used against the declared sorts, or a
+    /// symbol with the wrong number of arguments. Bound variables take the sort
+    /// their quantifier is annotated with.
+    pub fn check(&self, con: &Connective) -> Vec<SortError> {
+        let mut checker = Checker {
+            signature: self,
+            bound: vec![],
+            errors: vec![],
+        };
+        checker.visit_connective(con);
+        checker.errors
+    }
+}
+
+/// Collects the sort errors of a formula, keeping track of the sorts of the
+/// variables bound where it is.
+struct Checker<'a> {
+    signature: &'a Signature,
+    bound: Vec<(String, Option<String>)>,
+    errors: Vec<SortError>,
+}
+
+impl Visitor for Checker<'_> {
+    fn visit_connective(&mut self, con: &Connective) {
+        match con {
+            Connective::Predicate(p, args) => {
+                let expected = self.signature.predicate_sorts(p);
+                self.check_arguments(p, expected, args);
+            }
+            Connective::Eq(a, b) => {
+                let a_sort = self.check_term(a);
+                let b_sort = self.check_term(b);
+                if let (Some(expected), Some(found)) = (a_sort, b_sort) {
+                    if expected != found {
+                        self.errors.push(SortError::Mismatch {
+                            symbol: "≈".to_string(),
+                            term: b.clone(),
+                            expected,
+                            found,
+                        });
+                    }
+                }
+            }
+            Connective::ForAll(x, sort, body) | Connective::Exists(x, sort, body) => {
+                self.bound.push((x.clone(), sort.clone()));
+                self.visit_connective(body);
+                self.bound.pop();
+            }
+            _ => walk_connective(self, con),
+        }
+    }
+}
+
+impl Checker<'_> {
+    /// Checks the arguments of a predicate or function against the sorts declared
+    /// for them, if any.
+    fn check_arguments(&mut self, symbol: &str, expected: Option<&[String]>, args: &[Term]) {
+        let found: Vec<_> = args.iter().map(|arg| self.check_term(arg)).collect();
+        let expected = match expected {
+            Some(expected) => expected,
+            None => return,
+        };
+        if expected.len() != args.len() {
+            self.errors.push(SortError::Arity {
+                symbol: symbol.to_string(),
+                expected: expected.len(),
+                found: args.len(),
+            });
+            return;
+        }
+        for ((arg, found), expected) in args.iter().zip(found).zip(expected) {
+            if let Some(found) = found.filter(|found| found != expected) {
+                self.errors.push(SortError::Mismatch {
+                    symbol: symbol.to_string(),
+                    term: arg.clone(),
+                    expected: expected.clone(),
+                    found,
+                });
+            }
+        }
+    }
+
+    /// The sort of a term, checking the arguments of any function in it.
+    fn check_term(&mut self, term: &Term) -> Option<String> {
+        match term {
+            Term::Var(x) => self
+                .bound
+                .iter()
+                .rev()
+                .find(|(name, _)| name == x)
+                .and_then(|(_, sort)| sort.clone()),
+            Term::Const(_) => self.signature.sort_of(term).map(str::to_string),
+            Term::Function(f, args) => {
+                let signature = self.signature;
+                let expected = signature.function_sorts(f).map(|(sorts, _)| sorts);
+                self.check_arguments(f, expected, args);
+                signature.sort_of(term).map(str::to_string)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+use crate::parse::parse;
+
+#[cfg(test)]
+fn pets() -> Signature {
+    Signature::new()
+        .predicate("Owns", &["Person", "Pet"])
+        .predicate("Barks", &["Pet"])
+        .function("owner", &["Pet"], "Person")
+        .constant("rex", "Pet")
+        .constant("ann", "Person")
+}
+
+#[test]
+fn well_sorted_formulas() {
+    let signature = pets();
+    for src in &[
+        "Owns(ann, rex)",
+        "∀x:Pet Owns(owner(x), x)",
+        "∃x:Person ∀y:Pet (Owns(x, y) → Barks(y))",
+        "∀x Barks(x)",
+        "owner(rex) ≈ ann",
+        "Unknown(rex, ann, bob)",
+    ] {
+        assert_eq!(signature.check(&parse(src).unwrap()), vec![], "{}", src);
+    }
+}
+
+#[test]
+fn sort_mismatches() {
+    let signature = pets();
+    assert_eq!(
+        signature.check(&parse("Owns(rex, ann)").unwrap()),
+        vec![
+            SortError::Mismatch {
+                symbol: "Owns".to_string(),
+                term: Term::Const("rex".to_string()),
+                expected: "Person".to_string(),
+                found: "Pet".to_string(),
+            },
+            SortError::Mismatch {
+                symbol: "Owns".to_string(),
+                term: Term::Const("ann".to_string()),
+                expected: "Pet".to_string(),
+                found: "Person".to_string(),
+            },
+        ]
+    );
+
+    let errors = signature.check(&parse("∀x:Person Barks(owner(x))").unwrap());
+    assert_eq!(errors.len(), 2);
+    assert_eq!(
+        errors[0].message(),
+        "`x` is of sort Person but owner expects Pet"
+    );
+    assert_eq!(
+        errors[1].message(),
+        "`owner(x)` is of sort Person but Barks expects Pet"
+    );
+
+    assert_eq!(
+        signature.check(&parse("Barks(rex, rex)").unwrap()),
+        vec![SortError::Arity {
+            symbol: "Barks".to_string(),
+            expected: 1,
+            found: 2,
+        }]
+    );
+    assert_eq!(signature.check(&parse("rex ≈ ann").unwrap()).len(), 1);
+}
@@ -0,0 +1,131 @@
+use crate::ast::{Connective, Term};
+use crate::visit::{walk_connective, walk_term, Visitor};
+use indexmap::IndexSet;
+
+/// The names used in a formula, sorted by what they stand for, each in the order
+/// of first occurrence.
+#[derive(Default)]
+struct Symbols {
+    /// The variables bound by the quantifiers enclosing the current position.
+    scope: Vec<String>,
+    free_vars: IndexSet<String>,
+    bound_vars: IndexSet<String>,
+    constants: IndexSet<String>,
+    predicates: IndexSet<(String, usize)>,
+    propositions: IndexSet<String>,
+}
+
+impl Visitor for Symbols {
+    fn visit_connective(&mut self, con: &Connective) {
+        match con {
+            Connective::Var(x) => {
+                if !self.scope.contains(x) {
+                    self.propositions.insert(x.clone());
+                }
+            }
+            Connective::Predicate(p, args) => {
+                self.predicates.insert((p.clone(), args.len()));
+                walk_connective(self, con);
+            }
+            Connective::ForAll(x, _, inner) | Connective::Exists(x, _, inner) => {
+                self.bound_vars.insert(x.clone());
+                self.scope.push(x.clone());
+                self.visit_connective(inner);
+                self.scope.pop();
+            }
+            _ => walk_connective(self, con),
+        }
+    }
+
+    fn visit_term(&mut self, term: &Term) {
+        match term {
+            Term::Var(x) => {
+                if !self.scope.contains(x) {
+                    self.free_vars.insert(x.clone());
+                }
+            }
+            Term::Const(c) => {
+                self.constants.insert(c.clone());
+            }
+            Term::Function(_, _) => walk_term(self, term),
+        }
+    }
+}
+
+impl Connective {
+    fn symbols(&self) -> Symbols {
+        let mut symbols = Symbols::default();
+        symbols.visit_connective(self);
+        symbols
+    }
+
+    /// The variables used in terms outside of any quantifier binding them.
+    ///
+    /// The parser reads an unbound name as a constant unless a quantifier elsewhere
+    /// in the formula or argument binds it, as in `∀x P(x) ∧ Q(x)`.
+    pub fn free_vars(&self) -> Vec<String> {
+        self.symbols().free_vars.into_iter().collect()
+    }
+
+    /// The variables bound by a quantifier somewhere in the formula.
+    pub fn bound_vars(&self) -> Vec<String> {
+        self.symbols().bound_vars.into_iter().collect()
+    }
+
+    /// The constants used in terms, including inside function applications.
+    pub fn constants(&self) -> Vec<String> {
+        self.symbols().constants.into_iter().collect()
+    }
+
+    /// The predicates with the number of arguments they are applied to. A name used
+    /// with two arities is listed twice.
+    pub fn predicates(&self) -> Vec<(String, usize)> {
+        self.symbols().predicates.into_iter().collect()
+    }
+
+    /// The propositional atoms, leaving out those named like an enclosing bound
+    /// variable.
+    pub fn propositions(&self) -> Vec<String> {
+        self.symbols().propositions.into_iter().collect()
+    }
+
+    /// Whether the formula has no free variables.
+    pub fn is_sentence(&self) -> bool {
+        self.free_vars().is_empty()
+    }
+}
+
+#[cfg(test)]
+use crate::parse::parse;
+
+#[test]
+fn symbol_queries() {
+    let con = parse("∀x (P(x, f(a)) → ∃y Q(y, x)) ∧ r ∨ P(b) ∧ ∀r R(r)").unwrap();
+    assert_eq!(con.free_vars(), Vec::<String>::new());
+    assert_eq!(con.bound_vars(), vec!["x", "y", "r"]);
+    assert_eq!(con.constants(), vec!["a", "b"]);
+    assert_eq!(
+        con.predicates(),
+        vec![
+            ("P".to_string(), 2),
+            ("Q".to_string(), 2),
+            ("P".to_string(), 1),
+            ("R".to_string(), 1),
+        ]
+    );
+    assert_eq!(con.propositions(), vec!["r"]);
+    assert!(con.is_sentence());
+
+    let body = match parse("∀x ∃y (P(x, y) ∨ x)").unwrap() {
+        Connective::ForAll(_, _, body) => *body,
+        _ => unreachable!(),
+    };
+    assert_eq!(body.free_vars(), vec!["x"]);
+    assert_eq!(body.propositions(), vec!["x"]);
+    assert!(!body.is_sentence());
+
+    let con = parse("∀x P(x) ∧ Q(x, a)").unwrap();
+    assert_eq!(con.free_vars(), vec!["x"]);
+    assert_eq!(con.constants(), vec!["a"]);
+    assert!(!con.is_sentence());
+}
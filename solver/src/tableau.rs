@@ -1,10 +1,18 @@
-use crate::ast::Connective;
+use crate::arena::{Arena, Formula, FormulaId};
+use crate::ast::{Connective, Term};
 use crate::parse;
+use crate::sort::{compatible, Signature};
+use crate::PrettyOptions;
+use indexmap::IndexMap;
 use std::collections::{HashMap, HashSet, VecDeque};
 
+mod modal;
+
+pub use self::modal::{KripkeModel, ModalSystem, ModalTableau, World};
+
 #[derive(Debug, Clone)]
 struct Node {
-    connectives: Vec<(FactId, Connective, bool)>,
+    connectives: Vec<(FactId, FormulaId, bool)>,
     closed: bool,
 }
 
@@ -23,76 +31,111 @@ struct FactId(usize);
 
 #[derive(Debug, Clone)]
 enum QueueEntry {
-    Repeated(usize, FactId, Connective, bool, String, bool),
-    Standard(usize, FactId, Connective, bool),
+    Repeated(usize, FactId, FormulaId, bool, Term, bool),
+    Standard(usize, FactId, FormulaId, bool),
+    Rewritten(FactId, FormulaId, bool),
 }
 
 impl QueueEntry {
-    fn extract(&self) -> (&Connective, bool, Option<&str>) {
+    fn extract(&self) -> (FormulaId, bool, Option<&Term>) {
         match self {
-            QueueEntry::Standard(_, _, con, expect) => (con, *expect, None),
-            QueueEntry::Repeated(_, _, con, expect, to_repace, _) => (con, *expect, Some(to_repace)),
+            QueueEntry::Standard(_, _, con, expect) | QueueEntry::Rewritten(_, con, expect) => {
+                (*con, *expect, None)
+            }
+            QueueEntry::Repeated(_, _, con, expect, to_repace, _) => {
+                (*con, *expect, Some(to_repace))
+            }
         }
     }
 }
 
 #[derive(Debug, Clone)]
 struct Knowlage {
-    facts: HashMap<Connective, bool>,
-    queue: VecDeque<(FactId, Connective, bool)>,
-    known_constants: HashSet<String>,
-    repeaters: Vec<(FactId, String, Connective, bool, HashSet<String>)>,
+    /// The facts on the branch, keyed by their alpha-canonical form so that facts
+    /// which only differ in the names of bound variables meet.
+    facts: HashMap<FormulaId, bool>,
+    queue: VecDeque<(FactId, FormulaId, bool)>,
+    known_terms: IndexMap<Term, Option<String>>,
+    repeaters: Vec<(FactId, String, Option<String>, FormulaId, bool, HashSet<Term>)>,
+    equalities: Vec<(FactId, Term, Term)>,
+    literals: Vec<(FormulaId, bool)>,
 }
 
 impl Knowlage {
-    fn new(known_constants: HashSet<String>) -> Knowlage {
+    fn new(known_terms: IndexMap<Term, Option<String>>) -> Knowlage {
         Knowlage {
             queue: Default::default(),
             facts: Default::default(),
-            known_constants,
+            known_terms,
             repeaters: vec![],
+            equalities: vec![],
+            literals: vec![],
         }
     }
-    fn generate_queue(&self) -> Vec<QueueEntry> {
-        let queued = self
+    fn generate_queue(&self, arena: &mut Arena) -> Vec<QueueEntry> {
+        let mut entries: Vec<_> = self
             .queue
             .iter()
             .cloned()
             .enumerate()
             .map(|(i, (fact_id, connective, expect))| {
                 QueueEntry::Standard(i, fact_id, connective, expect)
-            });
-        let repeated = self.repeaters.iter().enumerate().flat_map(
-            |(i, (fact_id, to_repalce, con, expect, ran_on))| {
-                // if self.known_constants.is_empty() {
-                //     vec![QueueEntry::Repeated(i, *fact_id, con.clone(), *expect, to_repalce.to_string(), true)]
-                // } else {
-                    self.known_constants
-                        .difference(&ran_on)
-                        .map(move |constant| {
-                            QueueEntry::Repeated(
-                                i,
-                                *fact_id,
-                                con.substitude(&to_repalce, &constant),
-                                *expect,
-                                constant.to_string(),
-                                false,
-                            )
-                        })
-                // }
-            },
-        );
-        queued.chain(repeated).collect()
+            })
+            .collect();
+        for (i, (fact_id, to_repalce, sort, con, expect, ran_on)) in
+            self.repeaters.iter().enumerate()
+        {
+            for (term, term_sort) in &self.known_terms {
+                if ran_on.contains(term)
+                    || !compatible(
+                        term_sort.as_ref().map(String::as_str),
+                        sort.as_ref().map(String::as_str),
+                    )
+                {
+                    continue;
+                }
+                let instance = arena.substitude(*con, to_repalce, term);
+                arena.alpha_canonical(instance);
+                entries.push(QueueEntry::Repeated(
+                    i,
+                    *fact_id,
+                    instance,
+                    *expect,
+                    term.clone(),
+                    false,
+                ));
+            }
+        }
+        for (fact_id, s, t) in &self.equalities {
+            for (con, expect) in &self.literals {
+                for &(from, to) in &[(s, t), (t, s)] {
+                    let con = match rewrite(arena, *con, from, to) {
+                        Some(con) => con,
+                        None => continue,
+                    };
+                    arena.alpha_canonical(con);
+                    let keep = match &arena[con] {
+                        Formula::Eq(a, b) if *expect && a == b => false,
+                        _ => self.known(arena, con) != Some(*expect),
+                    };
+                    if keep {
+                        entries.push(QueueEntry::Rewritten(*fact_id, con, *expect));
+                    }
+                }
+            }
+        }
+        entries
     }
-    fn process_queue_entry(&mut self, entry: QueueEntry) -> (FactId, Connective, bool, bool) {
+    fn process_queue_entry(&mut self, entry: QueueEntry) -> (FactId, FormulaId, bool, bool) {
         match entry {
-            QueueEntry::Repeated(index, fact_id, connective, expect, constant, introduce_constant) => {
+            QueueEntry::Repeated(index, fact_id, connective, expect, term, introduce_constant) => {
                 if introduce_constant {
-                    self.known_constants.insert(constant.to_string());
+                    let sort = self.repeaters[index].2.clone();
+                    self.known_terms.insert(term.clone(), sort);
                 }
 
                 if let Some(repeater) = self.repeaters.get_mut(index) {
-                    repeater.4.insert(constant);
+                    repeater.5.insert(term);
                 } else {
                     panic!("repeater did not exists");
                 }
@@ -102,79 +145,153 @@ impl Knowlage {
                 self.queue.remove(index);
                 (fact_id, connective, expect, false)
             }
+            QueueEntry::Rewritten(fact_id, connective, expect) => (fact_id, connective, expect, true),
         }
     }
-    fn pop(&mut self) -> Option<(FactId, Connective, bool, bool)> {
-        let queue = self.generate_queue();
+    fn pop(&mut self, arena: &mut Arena) -> Option<(FactId, FormulaId, bool, bool)> {
+        let queue = self.generate_queue(arena);
 
+        let entry = queue
+            .into_iter()
+            .min_by_key(|entry| self.priority(arena, entry))?;
 
-        let entry = queue.clone().into_iter().min_by_key(|entry| {
-            let (con, expect, repeated) = entry.extract();
-
-            let contra = |con, expect: bool| self.facts.get(con).cloned() == Some(!expect);
-
-            match (con, expect, repeated) {
-                (Connective::Var(_), _, _) => 1,
-                (Connective::And(a, b), true, _) if contra(a, true) || contra(b, true) => 0,
-                (Connective::Or(a, b), false, _) if contra(a, false) || contra(b, false) => 0,
-                (Connective::And(_, _), true, _) |
-                (Connective::Or(_, _), false, _) => 2,
-                (Connective::Implicate(a, b), false, _) if contra(a, true) || contra(b, false) => 0,
-                (Connective::Implicate(_, _), false, _) => 3,
-                (Connective::Implicate(a, b), true, _) if contra(a, false) || contra(b, true) => 0,
-                _ => 100
-            }
-        })?;
-        // let entry = queue.into_iter().next()?;
-
-        // let pretty_entry = |entry: &QueueEntry| {
-        //     match entry {
-        //         QueueEntry::Standard(_, _, con, expect) => format!("S({}: {})", con.pretty(), expect),
-        //         QueueEntry::Repeated(_, _, con, expect, _, _) => format!("R({}: {})", con.pretty(), expect),
-        //     }
-        // };
+        Some(self.process_queue_entry(entry))
+    }
+    /// How soon `entry` should be processed, lowest first. Entries which close the
+    /// branch right away come first and γ-instantiations last.
+    fn priority(&self, arena: &Arena, entry: &QueueEntry) -> usize {
+        let (con, expect, repeated) = entry.extract();
 
-        // println!("{:?}, choose {:?}", queue.iter().map(pretty_entry).collect::<Vec<_>>().join(", "), pretty_entry(&entry));
+        let contra = |con: &FormulaId, expect: bool| self.known(arena, *con) == Some(!expect);
+        if contra(&con, expect) {
+            return 0;
+        }
 
-        Some(self.process_queue_entry(entry))
+        match (&arena[con], expect, repeated) {
+            (Formula::Var(_), _, _) => 1,
+            (Formula::And(a, b), true, _) if contra(a, true) || contra(b, true) => 0,
+            (Formula::Or(a, b), false, _) if contra(a, false) || contra(b, false) => 0,
+            (Formula::And(_, _), true, _) |
+            (Formula::Or(_, _), false, _) => 2,
+            (Formula::Implicate(a, b), false, _) if contra(a, true) || contra(b, false) => 0,
+            (Formula::Implicate(_, _), false, _) => 3,
+            (Formula::Implicate(a, b), true, _) if contra(a, false) || contra(b, true) => 0,
+            (Formula::Predicate(_, _), _, None) | (Formula::Eq(_, _), _, None) => 50,
+            (_, _, Some(term)) => 100 + term.depth(),
+            _ => 100
+        }
+    }
+    /// The value `connective`, or a formula alpha-equivalent to it, has on the branch.
+    /// Every formula is canonicalised before it is queued, so its canonical form is
+    /// already in the arena.
+    fn known(&self, arena: &Arena, connective: FormulaId) -> Option<bool> {
+        let canonical = arena.canonical(connective)?;
+        self.facts.get(&canonical).cloned()
     }
-    fn fact(&mut self, connective: Connective, expect: bool) -> Result<bool, FactResult> {
-        if let Some(fact) = self.facts.get(&connective) {
-            if expect == *fact {
+    fn fact(
+        &mut self,
+        arena: &mut Arena,
+        connective: FormulaId,
+        expect: bool,
+    ) -> Result<bool, FactResult> {
+        match (&arena[connective], expect) {
+            (Formula::Bottom, true) | (Formula::Top, false) => return Err(FactResult::Closes),
+            (Formula::Eq(a, b), false) if a == b => return Err(FactResult::Closes),
+            _ => {}
+        }
+        let canonical = arena.alpha_canonical(connective);
+        if let Some(fact) = self.facts.get(&canonical).cloned() {
+            if expect == fact {
                 Ok(false)
             } else {
                 Err(FactResult::Closes)
             }
         } else {
-            self.facts.insert(connective, expect);
+            if let Formula::Predicate(_, _) | Formula::Eq(_, _) = arena[connective] {
+                self.literals.push((connective, expect));
+            }
+            self.facts.insert(canonical, expect);
             Ok(true)
         }
     }
-    fn queue(&mut self, fact_id: FactId, connective: Connective, expect: bool) {
+    fn queue(&mut self, fact_id: FactId, connective: FormulaId, expect: bool) {
         self.queue.push_back((fact_id, connective, expect));
     }
     fn add_repeater(
         &mut self,
         fact_id: FactId,
         to_repalce: String,
-        connective: Connective,
+        sort: Option<String>,
+        connective: FormulaId,
         expect: bool,
     ) {
         self.repeaters
-            .push((fact_id, to_repalce, connective, expect, HashSet::new()))
+            .push((fact_id, to_repalce, sort, connective, expect, HashSet::new()))
     }
-    fn register_constant(&mut self, constant: String) {
-        self.known_constants.insert(constant);
+    fn register_term(&mut self, term: Term, sort: Option<String>) {
+        self.known_terms.entry(term).or_insert(sort);
+    }
+    fn add_equality(&mut self, fact_id: FactId, connective: &Formula) {
+        if let Formula::Eq(a, b) = connective {
+            self.equalities.push((fact_id, a.clone(), b.clone()));
+        }
     }
 }
 
+/// Rewrites the occurrences of `from` to `to` in the arguments of a predicate or
+/// equality, if there are any.
+fn rewrite(arena: &mut Arena, con: FormulaId, from: &Term, to: &Term) -> Option<FormulaId> {
+    let rewritten = match &arena[con] {
+        Formula::Predicate(p, args) if args.iter().any(|arg| arg.contains(from)) => {
+            Formula::Predicate(p.clone(), args.iter().map(|arg| arg.replace(from, to)).collect())
+        }
+        Formula::Eq(a, b) if a.contains(from) || b.contains(from) => {
+            Formula::Eq(a.replace(from, to), b.replace(from, to))
+        }
+        _ => return None,
+    };
+    Some(arena.add(rewritten))
+}
+
 #[derive(Debug, Clone, PartialEq)]
 enum FactResult {
     Closes,
 }
 
+/// Something about a tableau that may not be what was meant.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Warning {
+    /// `formula` has free variables, which are never instantiated and so act like
+    /// constants no γ-rule knows about.
+    OpenFormula {
+        formula: Connective,
+        free_vars: Vec<String>,
+    },
+    /// The tableau ran out of steps, facts, constants or worlds before every branch
+    /// was closed or worked out, so it being open proves nothing.
+    Unfinished,
+}
+
+impl Warning {
+    pub fn message(&self) -> String {
+        match self {
+            Warning::OpenFormula { formula, free_vars } => format!(
+                "`{}` is not a sentence, {} {} free",
+                formula.pretty(),
+                free_vars.join(", "),
+                if free_vars.len() == 1 { "is" } else { "are" }
+            ),
+            Warning::Unfinished => {
+                "the tableau was stopped before every branch was worked out".to_string()
+            }
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Tableau {
+    /// Every formula on the tableau, which the nodes and knowlage refer to by id.
+    arena: Arena,
     facts_counter: usize,
     constant_counter: usize,
     process_counter: usize,
@@ -182,23 +299,51 @@ pub struct Tableau {
     knowlage_stack: Vec<Knowlage>,
     nodes: Vec<Node>,
     edges: Vec<Edge>,
+    signature: Signature,
+    warnings: Vec<Warning>,
 }
 
 impl Tableau {
     pub fn new(start: Vec<(Connective, bool)>) -> Tableau {
-        let known_constants: HashSet<_> = start
+        Tableau::with_signature(start, &Signature::default())
+    }
+    /// A tableau whose γ-rules only instantiate a quantifier annotated with a sort
+    /// by terms that `signature` gives the same sort, or no sort at all.
+    pub fn with_signature(start: Vec<(Connective, bool)>, signature: &Signature) -> Tableau {
+        let known_terms: IndexMap<_, _> = start
             .iter()
-            .flat_map(|(con, _)| con.all_variables().into_iter())
+            .flat_map(|(con, _)| con.ground_terms().into_iter())
+            .map(|term| {
+                let sort = signature.sort_of(&term).map(str::to_string);
+                (term, sort)
+            })
+            .collect();
+        let warnings = start
+            .iter()
+            .filter(|(con, _)| !con.is_sentence())
+            .map(|(con, _)| Warning::OpenFormula {
+                formula: con.clone(),
+                free_vars: con.free_vars(),
+            })
+            .collect();
+
+        let mut arena = Arena::new();
+        let start: Vec<_> = start
+            .iter()
+            .map(|(con, expect)| (arena.intern(con), *expect))
             .collect();
 
         let mut tableau = Tableau {
+            arena,
             facts_counter: 0,
             constant_counter: 0,
             process_counter: 0,
-            knowlage: Knowlage::new(known_constants),
+            knowlage: Knowlage::new(known_terms),
             knowlage_stack: vec![],
             nodes: vec![],
             edges: vec![],
+            signature: signature.clone(),
+            warnings,
         };
 
         let (staring_node_id, node) = tableau.alloc_node(start.clone());
@@ -209,17 +354,25 @@ impl Tableau {
     }
     fn queue_facts(
         &mut self,
-        connectives: impl IntoIterator<Item = (FactId, Connective, bool)>,
+        connectives: impl IntoIterator<Item = (FactId, FormulaId, bool)>,
     ) -> Result<(), FactResult> {
         for (fact_id, con, expect) in connectives {
-            if self.knowlage.fact(con.clone(), expect)? {
+            // γ-rules are instantiated with every ground term seen on the branch
+            for term in self.arena.ground_terms(con) {
+                let sort = self.signature.sort_of(&term).map(str::to_string);
+                self.knowlage.register_term(term, sort);
+            }
+            if self.knowlage.fact(&mut self.arena, con, expect)? {
+                if expect {
+                    self.knowlage.add_equality(fact_id, &self.arena[con]);
+                }
                 self.knowlage.queue(fact_id, con, expect);
             }
         }
         Ok(())
     }
-    fn pop_queue(&mut self) -> Option<(FactId, Connective, bool, bool)> {
-        self.knowlage.pop()
+    fn pop_queue(&mut self) -> Option<(FactId, FormulaId, bool, bool)> {
+        self.knowlage.pop(&mut self.arena)
     }
     fn create_edge(&mut self, origin_node: NodeId, fact: FactId, to: NodeId) {
         self.edges.push(Edge {
@@ -238,14 +391,14 @@ impl Tableau {
     }
     fn alloc_node(
         &mut self,
-        connectives: impl IntoIterator<Item = (Connective, bool)>,
+        connectives: impl IntoIterator<Item = (FormulaId, bool)>,
     ) -> (NodeId, &Node) {
         let connectives = connectives
             .into_iter()
             .map(|(con, expect)| {
                 let fact_id = self.facts_counter;
                 self.facts_counter += 1;
-                (FactId(fact_id), con.clone(), expect)
+                (FactId(fact_id), con, expect)
             })
             .collect();
 
@@ -256,23 +409,19 @@ impl Tableau {
         });
         (node_id, &self.nodes[node_id.0])
     }
-    fn alloc_constant(&mut self) -> String {
-        let con = format!("C{}", self.constant_counter);
-        self.knowlage.register_constant(con.clone());
+    fn alloc_constant(&mut self, sort: Option<String>) -> Term {
+        let con = Term::Const(format!("C{}", self.constant_counter));
+        self.knowlage.register_term(con.clone(), sort);
         self.constant_counter += 1;
         // println!("{:?}", self.constant_counter);
         con
     }
     fn process_next(&mut self, from: NodeId, last_result: Result<(), FactResult>) -> bool {
-        if self.process_counter >= 300 {
-            // println!("Exeed process limit");
-            return false;
-        }
-        if self.constant_counter >= 100 {
-            return false;
-        }
-        if self.facts_counter >= 100 {
-            // println!("more then 100 facts");
+        if self.process_counter >= 300 || self.constant_counter >= 100 || self.facts_counter >= 100
+        {
+            if !self.warnings.contains(&Warning::Unfinished) {
+                self.warnings.push(Warning::Unfinished);
+            }
             return false;
         }
         self.process_counter += 1;
@@ -298,75 +447,124 @@ impl Tableau {
         &mut self,
         from: NodeId,
         fact_id: FactId,
-        connective: Connective,
+        connective: FormulaId,
         expect: bool,
     ) -> bool {
-        match connective {
-            Connective::Var(_) | Connective::Predicate(_, _) => self.process_next(from, Ok(())),
-            Connective::And(left, right) => {
+        match self.arena[connective].clone() {
+            Formula::Var(_)
+            | Formula::Predicate(_, _)
+            | Formula::Eq(_, _)
+            | Formula::Top
+            | Formula::Bottom
+            | Formula::Error
+            | Formula::Box(_)
+            | Formula::Diamond(_)
+            | Formula::Next(_)
+            | Formula::Eventually(_)
+            | Formula::Always(_)
+            | Formula::Until(_, _) => self.process_next(from, Ok(())),
+            Formula::And(left, right) => {
                 if expect {
-                    self.straight(from, fact_id, vec![(*left, true), (*right, true)])
+                    self.straight(from, fact_id, vec![(left, true), (right, true)])
                 } else {
-                    self.branch(from, fact_id, vec![(*left, false)], vec![(*right, false)])
+                    self.branch(from, fact_id, vec![(left, false)], vec![(right, false)])
                 }
             }
-            Connective::Or(left, right) => {
+            Formula::Or(left, right) => {
                 if expect {
-                    self.branch(from, fact_id, vec![(*left, true)], vec![(*right, true)])
+                    self.branch(from, fact_id, vec![(left, true)], vec![(right, true)])
                 } else {
-                    self.straight(from, fact_id, vec![(*left, false), (*right, false)])
+                    self.straight(from, fact_id, vec![(left, false), (right, false)])
                 }
             }
-            Connective::Implicate(left, right) => {
+            Formula::Implicate(left, right) => {
                 if expect {
-                    self.branch(from, fact_id, vec![(*left, false)], vec![(*right, true)])
+                    self.branch(from, fact_id, vec![(left, false)], vec![(right, true)])
                 } else {
-                    self.straight(from, fact_id, vec![(*left, true), (*right, false)])
+                    self.straight(from, fact_id, vec![(left, true), (right, false)])
                 }
             }
-            Connective::Biimplicate(left, right) => {
+            Formula::Biimplicate(left, right) => {
                 if expect {
                     self.branch(
                         from,
                         fact_id,
-                        vec![(*left.clone(), false), (*right.clone(), false)],
-                        vec![(*left, true), (*right, true)],
+                        vec![(left, false), (right, false)],
+                        vec![(left, true), (right, true)],
                     )
                 } else {
                     self.branch(
                         from,
                         fact_id,
-                        vec![(*left.clone(), false), (*right.clone(), true)],
-                        vec![(*left, true), (*right, false)],
+                        vec![(left, false), (right, true)],
+                        vec![(left, true), (right, false)],
                     )
                 }
             }
-            Connective::Not(con) => self.straight(from, fact_id, vec![(*con, !expect)]),
-            Connective::Exists(var, con) => {
+            Formula::Xor(left, right) => {
                 if expect {
-                    let new_const = self.alloc_constant();
-                    let new_con = con.substitude(&var, &new_const);
+                    self.branch(
+                        from,
+                        fact_id,
+                        vec![(left, false), (right, true)],
+                        vec![(left, true), (right, false)],
+                    )
+                } else {
+                    self.branch(
+                        from,
+                        fact_id,
+                        vec![(left, false), (right, false)],
+                        vec![(left, true), (right, true)],
+                    )
+                }
+            }
+            Formula::Nand(left, right) => {
+                if expect {
+                    self.branch(from, fact_id, vec![(left, false)], vec![(right, false)])
+                } else {
+                    self.straight(from, fact_id, vec![(left, true), (right, true)])
+                }
+            }
+            Formula::Nor(left, right) => {
+                if expect {
+                    self.straight(from, fact_id, vec![(left, false), (right, false)])
+                } else {
+                    self.branch(from, fact_id, vec![(left, true)], vec![(right, true)])
+                }
+            }
+            Formula::ReverseImplicate(left, right) => {
+                if expect {
+                    self.branch(from, fact_id, vec![(left, true)], vec![(right, false)])
+                } else {
+                    self.straight(from, fact_id, vec![(left, false), (right, true)])
+                }
+            }
+            Formula::Not(con) => self.straight(from, fact_id, vec![(con, !expect)]),
+            Formula::Exists(var, sort, con) => {
+                if expect {
+                    let new_const = self.alloc_constant(sort);
+                    let new_con = self.arena.substitude(con, &var, &new_const);
                     self.straight(from, fact_id, vec![(new_con, expect)])
                 } else {
                     self.knowlage
-                        .add_repeater(fact_id, var.clone(), *con.clone(), expect);
+                        .add_repeater(fact_id, var.clone(), sort, con, expect);
                     self.straight(from, fact_id, vec![])
                 }
             }
-            Connective::ForAll(var, con) => {
+            Formula::ForAll(var, sort, con) => {
                 if expect {
                     self.knowlage
-                        .add_repeater(fact_id, var.clone(), *con.clone(), expect);
+                        .add_repeater(fact_id, var.clone(), sort, con, expect);
                     self.straight(from, fact_id, vec![])
                 } else {
-                    let new_const = self.alloc_constant();
-                    let new_con = con.substitude(&var, &new_const);
+                    let new_const = self.alloc_constant(sort);
+                    let new_con = self.arena.substitude(con, &var, &new_const);
                     self.straight(from, fact_id, vec![(new_con, expect)])
                 }
             }
         }
     }
-    fn straight(&mut self, from: NodeId, fact_id: FactId, cons: Vec<(Connective, bool)>) -> bool {
+    fn straight(&mut self, from: NodeId, fact_id: FactId, cons: Vec<(FormulaId, bool)>) -> bool {
         if !cons.is_empty() {
             let (node_id, node) = self.alloc_node(cons);
             let connectives = node.connectives.clone();
@@ -382,8 +580,8 @@ impl Tableau {
         &mut self,
         from: NodeId,
         fact_id: FactId,
-        left: Vec<(Connective, bool)>,
-        right: Vec<(Connective, bool)>,
+        left: Vec<(FormulaId, bool)>,
+        right: Vec<(FormulaId, bool)>,
     ) -> bool {
         self.save_knowlage();
         self.straight(from, fact_id, left);
@@ -393,47 +591,79 @@ impl Tableau {
         self.restore_knowlage();
         true
     }
+    /// Problems with the starting facts, such as formulas with free variables, and
+    /// [`Warning::Unfinished`] if the tableau was cut short.
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+    /// Whether every branch of the tableau ends in a contradiction. An unfinished
+    /// tableau is never closed, see [`Tableau::warnings`].
+    pub fn is_closed(&self) -> bool {
+        self.nodes.iter().enumerate().all(|(id, node)| {
+            node.closed || self.edges.iter().any(|edge| edge.origin_node.0 == id)
+        })
+    }
     pub fn generate_dot(&self) -> String {
-        let header = "digraph A {\n\t";
-        let footer = "\t\n}";
+        self.generate_dot_with(&PrettyOptions::default())
+    }
 
-        let nodes = self
-            .nodes
-            .iter()
-            .enumerate()
-            .map(|(id, node)| {
-                let mut label = node
-                    .connectives
-                    .iter()
-                    .map(|(id, con, expect)| {
-                        format!(r#"[{}] {}: {}"#, id.0 + 1, con.pretty(), expect)
-                    })
-                    .collect::<Vec<_>>()
-                    .join("\\n");
-                if node.closed {
-                    label += "\\nx";
-                }
-                format!(r#"{} [label="{}"];"#, id, label)
-            })
-            .collect::<Vec<_>>()
-            .join("\n\t");
+    /// The tableau as a Graphviz graph, with the facts printed using `options`,
+    /// e.g. to show uses of definitions by name.
+    pub fn generate_dot_with(&self, options: &PrettyOptions) -> String {
+        let nodes = self.nodes.iter().map(|node| {
+            let facts = node
+                .connectives
+                .iter()
+                .map(|(id, con, expect)| {
+                    let con = self.arena.get(*con).pretty_with(options);
+                    (*id, format!("{}: {}", con, expect))
+                })
+                .collect();
+            (facts, node.closed)
+        });
+        render_dot(nodes, &self.edges)
+    }
+}
 
-        let edges = self
-            .edges
-            .iter()
-            .map(|edge| {
-                format!(
-                    "{} -> {} [label=\"{}\"];",
-                    edge.origin_node.0,
-                    edge.to.0,
-                    edge.fact.0 + 1
-                )
-            })
-            .collect::<Vec<_>>()
-            .join("\n\t");
+/// A tree of nodes, each a list of numbered facts and whether it closes its
+/// branch, in DOT format. Edges are labelled with the fact that was expanded.
+fn render_dot(
+    nodes: impl Iterator<Item = (Vec<(FactId, String)>, bool)>,
+    edges: &[Edge],
+) -> String {
+    let header = "digraph A {\n\t";
+    let footer = "\t\n}";
 
-        format!("{}{}\n\n\t{}{}", header, nodes, edges, footer)
-    }
+    let nodes = nodes
+        .enumerate()
+        .map(|(id, (facts, closed))| {
+            let mut label = facts
+                .iter()
+                .map(|(id, fact)| format!("[{}] {}", id.0 + 1, fact))
+                .collect::<Vec<_>>()
+                .join("\\n");
+            if closed {
+                label += "\\nx";
+            }
+            format!(r#"{} [label="{}"];"#, id, label)
+        })
+        .collect::<Vec<_>>()
+        .join("\n\t");
+
+    let edges = edges
+        .iter()
+        .map(|edge| {
+            format!(
+                "{} -> {} [label=\"{}\"];",
+                edge.origin_node.0,
+                edge.to.0,
+                edge.fact.0 + 1
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n\t");
+
+    format!("{}{}\n\n\t{}{}", header, nodes, edges, footer)
 }
 
 #[allow(unused)]
@@ -474,6 +704,135 @@ fn other_test() {
     unimplemented!();
 }
 
+#[test]
+fn truth_constants_close_branches() {
+    assert!(run("⊥", true).is_closed());
+    assert!(run("p | ⊤", false).is_closed());
+    assert!(!run("p & ⊤", false).is_closed());
+    assert!(run("(p > ⊥) > !p", false).is_closed());
+}
+
+#[test]
+fn instantiates_with_ground_terms() {
+    assert!(parse::parse_argument("\\x (P(x) > P(f(x))), P(a) / P(f(f(a)))")
+        .unwrap()
+        .tableau()
+        .is_closed());
+    assert!(parse::parse_argument(".x P(f(x)), \\y (P(y) > Q(g(y))) / .z Q(z)")
+        .unwrap()
+        .tableau()
+        .is_closed());
+    assert!(!parse::parse_argument("\\x P(f(x)) / P(a)").unwrap().tableau().is_closed());
+}
+
+#[test]
+fn instantiation_renames_clashing_bound_variables() {
+    // Parsed separately, so that the free `y` is a constant.
+    let con = |src| parse::parse(src).unwrap();
+    let tableau = Tableau::new(vec![
+        (con("∀x ∃y R(x, y)"), true),
+        (con("S(y)"), true),
+        (con("∃w R(y, w)"), false),
+    ]);
+    assert!(tableau.is_closed());
+    let dot = tableau.generate_dot();
+    assert!(dot.contains("∃y1 R(y, y1): true"));
+    assert!(!dot.contains("∃y R(y, y)"));
+}
+
+#[test]
+fn warns_about_open_formulas() {
+    let con = parse::parse("∀x (P(x) ∧ ∃y R(x, y))").unwrap();
+    assert!(Tableau::new(vec![(con.clone(), true)]).warnings().is_empty());
+
+    let body = match con {
+        Connective::ForAll(_, _, body) => *body,
+        _ => unreachable!(),
+    };
+    let tableau = Tableau::new(vec![(body.clone(), true)]);
+    assert_eq!(
+        tableau.warnings(),
+        &[Warning::OpenFormula {
+            formula: body,
+            free_vars: vec!["x".to_string()],
+        }]
+    );
+    assert_eq!(
+        tableau.warnings()[0].message(),
+        "`P(x) ∧ ∃y R(x, y)` is not a sentence, x is free"
+    );
+
+    let argument = parse::parse_argument("∀x P(x) / P(x)").unwrap();
+    assert_eq!(
+        argument.tableau().warnings(),
+        &[Warning::OpenFormula {
+            formula: Connective::Predicate("P".to_string(), vec![Term::Var("x".to_string())]),
+            free_vars: vec!["x".to_string()],
+        }]
+    );
+}
+
+#[test]
+fn alpha_equivalent_facts_close_branches() {
+    let closes = |src| run(src, false).is_closed();
+    assert!(closes("□∀x P(x) → □∀y P(y)"));
+    assert!(closes("◇∃x (P(x) ∧ ∀y R(x, y)) → ◇∃z (P(z) ∧ ∀x R(z, x))"));
+    assert!(!closes("□∀x ∃y R(x, y) → □∀x ∃y R(y, x)"));
+}
+
+#[test]
+fn equality_rules() {
+    let closes = |src| parse::parse_argument(src).unwrap().tableau().is_closed();
+    assert!(closes("/ a == a"));
+    assert!(closes("a == b / b == a"));
+    assert!(closes("a == b, b == c / a == c"));
+    assert!(closes("a == b, P(f(a)) / P(f(b))"));
+    assert!(closes("\\x (f(x) == x), P(f(f(a))) / P(a)"));
+    assert!(closes("a == b, a ≠ b / ⊥"));
+    assert!(!closes("a == b / a == c"));
+    assert!(!closes("P(a) / P(b)"));
+}
+
+#[test]
+fn additional_connective_rules() {
+    let valid = |src| run(src, false).is_closed();
+    assert!(valid("(a ⊕ b) = !(a = b)"));
+    assert!(valid("(a ↑ b) = !(a & b)"));
+    assert!(valid("(a ↓ b) = !(a | b)"));
+    assert!(valid("(a ← b) = (b > a)"));
+    assert!(!valid("(a ⊕ b) = (a | b)"));
+    assert!(!valid("(a ↑ b) = (a ↓ b)"));
+}
+
+#[test]
+fn instantiates_only_with_terms_of_the_right_sort() {
+    let signature = Signature::new()
+        .constant("rex", "Pet")
+        .constant("ann", "Person");
+    let closes = |src, signature: &Signature| {
+        parse::parse_argument(src)
+            .unwrap()
+            .tableau_with(signature)
+            .is_closed()
+    };
+    assert!(closes("∀x:Pet Barks(x) / Barks(rex)", &signature));
+    assert!(!closes("∀x:Person Barks(x) / Barks(rex)", &signature));
+    assert!(closes("∀x:Person Barks(x) / Barks(rex)", &Signature::new()));
+    assert!(closes("∀x:Pet Barks(x), Barks(rex) > Wags(rex) / Wags(rex)", &signature));
+    assert!(closes("∃x:Pet Barks(x) / ∃y:Pet Barks(y)", &signature));
+    assert!(!closes("∃x:Pet Barks(x) / ∃y:Person Barks(y)", &signature));
+}
+
+#[test]
+fn argument_tableau() {
+    assert!(parse::parse_argument("p > q, p / q").unwrap().tableau().is_closed());
+    assert!(parse::parse_argument("(p & q) > r, !(p > r) / q > r")
+        .unwrap()
+        .tableau()
+        .is_closed());
+    assert!(!parse::parse_argument("p > q, q / p").unwrap().tableau().is_closed());
+}
+
 // #[test]
 // fn other_equiv_test() {
 //     let a = parse::parse("(\\x(.y(P(x, y))))").unwrap();
@@ -492,3 +851,32 @@ fn other_test() {
 //     println!("{}", run("(\\x(.y (P(y,x)))) > a", false).generate_dot());
 //     println!("end");
 // }
+
+#[test]
+fn dot_labels_can_fold_definitions() {
+    let mut options = parse::ParseOptions::default();
+    options.define("Refl(R) := ∀x R(x, x)").unwrap();
+    let tableau = Tableau::new(vec![(options.parse("Refl(Eq) > Eq(a, a)").unwrap(), false)]);
+    assert!(tableau.is_closed());
+
+    let pretty = PrettyOptions {
+        definitions: options.definitions.values().cloned().collect(),
+        ..PrettyOptions::default()
+    };
+    let dot = tableau.generate_dot_with(&pretty);
+    assert!(dot.contains("[2] Refl(Eq): true"));
+    assert!(!tableau.generate_dot().contains("Refl"));
+}
+
+#[test]
+fn running_out_of_steps() {
+    let src = (0..12)
+        .map(|i| format!("(a{} | b{})", i, i))
+        .collect::<Vec<_>>()
+        .join(" & ");
+    let tableau = run(&src, true);
+    assert!(!tableau.is_closed());
+    assert_eq!(tableau.warnings(), &[Warning::Unfinished]);
+
+    assert!(run("∀x P(x) → P(a)", false).warnings().is_empty());
+}
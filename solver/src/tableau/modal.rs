@@ -0,0 +1,639 @@
+use super::{render_dot, Edge, FactId, FactResult, NodeId, Warning};
+use crate::ast::Connective;
+use indexmap::{IndexMap, IndexSet};
+use std::collections::HashSet;
+use std::fmt;
+
+/// The normal modal logics a [`ModalTableau`] can decide, which differ in what
+/// the accessibility relation of their Kripke models has to be.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModalSystem {
+    /// Any relation.
+    K,
+    /// Reflexive relations, making `□p → p` valid.
+    T,
+    /// Reflexive and transitive relations, making `□p → □□p` valid as well.
+    S4,
+    /// Equivalence relations, where every world sees every other one.
+    S5,
+}
+
+impl ModalSystem {
+    fn reflexive(self) -> bool {
+        self != ModalSystem::K
+    }
+}
+
+/// A world of a Kripke model, written `w0`, `w1` and so on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct World(pub usize);
+
+impl fmt::Display for World {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "w{}", self.0)
+    }
+}
+
+/// A fact of a labelled tableau: either a formula being true or false in a world,
+/// or one world seeing another.
+#[derive(Debug, Clone, PartialEq)]
+enum Fact {
+    Holds(World, Connective, bool),
+    Access(World, World),
+}
+
+impl Fact {
+    fn pretty(&self) -> String {
+        match self {
+            Fact::Holds(world, con, expect) => format!("{}: {}: {}", world, con.pretty(), expect),
+            Fact::Access(from, to) => format!("{} R {}", from, to),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    facts: Vec<(FactId, Fact)>,
+    closed: bool,
+}
+
+/// Formulas the modal rules treat as atoms, which covers everything first-order or
+/// temporal.
+fn is_atom(con: &Connective) -> bool {
+    match con {
+        Connective::ForAll(_, _, _)
+        | Connective::Exists(_, _, _)
+        | Connective::Next(_)
+        | Connective::Eventually(_)
+        | Connective::Always(_)
+        | Connective::Until(_, _) => true,
+        con => con.is_atomic(),
+    }
+}
+
+/// The ways a propositional connective can be made `expect`, each a list of facts
+/// that have to hold together. One way is an α-rule and two a β-rule.
+fn alternatives(con: &Connective, expect: bool) -> Vec<Vec<(Connective, bool)>> {
+    let (a, b) = match con {
+        Connective::Not(x) => return vec![vec![(*x.clone(), !expect)]],
+        Connective::And(a, b)
+        | Connective::Or(a, b)
+        | Connective::Implicate(a, b)
+        | Connective::Biimplicate(a, b)
+        | Connective::Xor(a, b)
+        | Connective::Nand(a, b)
+        | Connective::Nor(a, b)
+        | Connective::ReverseImplicate(a, b) => (*a.clone(), *b.clone()),
+        _ => return vec![],
+    };
+    let both = |x: bool, y: bool| vec![vec![(a.clone(), x), (b.clone(), y)]];
+    let either = |x: bool, y: bool| vec![vec![(a.clone(), x)], vec![(b.clone(), y)]];
+    let equal = |same: bool| {
+        vec![
+            vec![(a.clone(), false), (b.clone(), !same)],
+            vec![(a.clone(), true), (b.clone(), same)],
+        ]
+    };
+    match (con, expect) {
+        (Connective::And(_, _), true) | (Connective::Nand(_, _), false) => both(true, true),
+        (Connective::And(_, _), false) | (Connective::Nand(_, _), true) => either(false, false),
+        (Connective::Or(_, _), true) | (Connective::Nor(_, _), false) => either(true, true),
+        (Connective::Or(_, _), false) | (Connective::Nor(_, _), true) => both(false, false),
+        (Connective::Implicate(_, _), true) => either(false, true),
+        (Connective::Implicate(_, _), false) => both(true, false),
+        (Connective::ReverseImplicate(_, _), true) => either(true, false),
+        (Connective::ReverseImplicate(_, _), false) => both(false, true),
+        (Connective::Biimplicate(_, _), expect) => equal(expect),
+        (Connective::Xor(_, _), expect) => equal(!expect),
+        _ => unreachable!(),
+    }
+}
+
+/// Everything known on one branch of a [`ModalTableau`].
+#[derive(Debug, Clone, Default)]
+struct Branch {
+    facts: IndexMap<(World, Connective), bool>,
+    /// Facts waiting for a propositional rule, or for a new world when they are
+    /// `□φ` false or `◇φ` true.
+    queue: Vec<(FactId, World, Connective, bool)>,
+    /// The facts `□φ` true and `◇φ` false, which carry `φ` over to every world
+    /// their world sees, with the worlds they were applied to so far.
+    universals: Vec<(FactId, World, Connective, bool, HashSet<World>)>,
+    worlds: usize,
+    access: Vec<(World, World)>,
+}
+
+impl Branch {
+    fn add(&mut self, fact_id: FactId, fact: Fact) -> Result<(), FactResult> {
+        let (world, con, expect) = match fact {
+            Fact::Access(from, to) => {
+                self.access.push((from, to));
+                return Ok(());
+            }
+            Fact::Holds(world, con, expect) => (world, con, expect),
+        };
+        match (&con, expect) {
+            (Connective::Bottom, true) | (Connective::Top, false) => {
+                return Err(FactResult::Closes)
+            }
+            _ => {}
+        }
+        match self.facts.get(&(world, con.clone())) {
+            Some(known) if *known == expect => return Ok(()),
+            Some(_) => return Err(FactResult::Closes),
+            None => {}
+        }
+        self.facts.insert((world, con.clone()), expect);
+        let universal = matches!(
+            (&con, expect),
+            (Connective::Box(_), true) | (Connective::Diamond(_), false)
+        );
+        if universal {
+            self.universals
+                .push((fact_id, world, con, expect, HashSet::new()));
+        } else if !is_atom(&con) {
+            self.queue.push((fact_id, world, con, expect));
+        }
+        Ok(())
+    }
+
+    fn holds(&self, world: World, con: &Connective, expect: bool) -> bool {
+        self.facts.get(&(world, con.clone())) == Some(&expect)
+    }
+
+    /// The worlds `world` sees directly. Transitivity in S4 is taken care of by
+    /// carrying `□φ` itself over to the next world along with `φ`.
+    fn successors(&self, system: ModalSystem, world: World) -> Vec<World> {
+        if system == ModalSystem::S5 {
+            return (0..self.worlds).map(World).collect();
+        }
+        let reflexive = Some(world).filter(|_| system.reflexive());
+        reflexive
+            .into_iter()
+            .chain(
+                self.access
+                    .iter()
+                    .filter(|(from, _)| *from == world)
+                    .map(|(_, to)| *to),
+            )
+            .collect()
+    }
+
+    fn parent(&self, world: World) -> Option<World> {
+        self.access
+            .iter()
+            .find(|(_, to)| *to == world)
+            .map(|(from, _)| *from)
+    }
+
+    /// An earlier world that already has every fact of `world`, so that a model
+    /// can reuse what it sees instead of giving `world` worlds of its own. Only S4
+    /// and S5 need this, since in K and T every new world has a smaller modal depth.
+    fn blocked_by(&self, system: ModalSystem, world: World) -> Option<World> {
+        let candidates: Vec<World> = match system {
+            ModalSystem::K | ModalSystem::T => return None,
+            ModalSystem::S4 => {
+                let mut ancestors = vec![];
+                let mut current = world;
+                while let Some(parent) = self.parent(current) {
+                    ancestors.push(parent);
+                    current = parent;
+                }
+                ancestors.into_iter().rev().collect()
+            }
+            ModalSystem::S5 => (0..world.0).map(World).collect(),
+        };
+        candidates.into_iter().find(|other| {
+            self.facts
+                .iter()
+                .filter(|((at, _), _)| *at == world)
+                .all(|((_, con), expect)| self.holds(*other, con, *expect))
+        })
+    }
+
+    /// The instances of a universal fact still to be added, or `None` once it has
+    /// been carried over to every world its world sees.
+    fn next_universal(&self, system: ModalSystem) -> Option<(usize, World, Vec<Fact>)> {
+        self.universals
+            .iter()
+            .enumerate()
+            .flat_map(|(i, (_, world, con, expect, applied))| {
+                self.successors(system, *world)
+                    .into_iter()
+                    .filter(move |to| !applied.contains(to))
+                    .map(move |to| {
+                        let inner = match con {
+                            Connective::Box(inner) | Connective::Diamond(inner) => &**inner,
+                            _ => unreachable!("only □ and ◇ are universal"),
+                        };
+                        let mut facts = vec![Fact::Holds(to, inner.clone(), *expect)];
+                        if system == ModalSystem::S4 && to != *world {
+                            facts.push(Fact::Holds(to, con.clone(), *expect));
+                        }
+                        (i, to, facts)
+                    })
+            })
+            .next()
+    }
+
+    fn model(&self, system: ModalSystem) -> KripkeModel {
+        let worlds = (0..self.worlds)
+            .map(|world| {
+                self.facts
+                    .iter()
+                    .filter(|((at, con), expect)| at.0 == world && **expect && is_atom(con))
+                    .map(|((_, con), _)| con.clone())
+                    .collect()
+            })
+            .collect();
+        let mut access: IndexSet<(World, World)> = match system {
+            ModalSystem::S5 => (0..self.worlds)
+                .flat_map(|a| (0..self.worlds).map(move |b| (World(a), World(b))))
+                .collect(),
+            _ => self.access.iter().cloned().collect(),
+        };
+        if system.reflexive() {
+            access.extend((0..self.worlds).map(|w| (World(w), World(w))));
+        }
+        if system == ModalSystem::S4 {
+            for w in (0..self.worlds).map(World) {
+                if let Some(other) = self.blocked_by(system, w) {
+                    access.insert((w, other));
+                }
+            }
+            loop {
+                let implied: Vec<_> = access
+                    .iter()
+                    .flat_map(|(a, b)| {
+                        access
+                            .iter()
+                            .filter(move |(c, _)| c == b)
+                            .map(move |(_, d)| (*a, *d))
+                    })
+                    .filter(|pair| !access.contains(pair))
+                    .collect();
+                if implied.is_empty() {
+                    break;
+                }
+                access.extend(implied);
+            }
+        }
+        let mut access: Vec<_> = access.into_iter().collect();
+        access.sort();
+        KripkeModel { worlds, access }
+    }
+}
+
+/// A Kripke model: worlds with the atoms true in each of them, and which worlds see
+/// which.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KripkeModel {
+    pub worlds: Vec<IndexSet<Connective>>,
+    pub access: Vec<(World, World)>,
+}
+
+impl KripkeModel {
+    /// Whether `con` is true in `world`, where an atom is true if the world lists it.
+    pub fn evaluate(&self, world: World, con: &Connective) -> bool {
+        let eval = |con: &Connective| self.evaluate(world, con);
+        let mut seen = self
+            .access
+            .iter()
+            .filter(|(from, _)| *from == world)
+            .map(|(_, to)| *to);
+        match con {
+            Connective::Top => true,
+            Connective::Bottom => false,
+            Connective::Box(x) => seen.all(|to| self.evaluate(to, x)),
+            Connective::Diamond(x) => seen.any(|to| self.evaluate(to, x)),
+            Connective::Not(x) => !eval(x),
+            Connective::And(a, b) => eval(a) && eval(b),
+            Connective::Or(a, b) => eval(a) || eval(b),
+            Connective::Implicate(a, b) => !eval(a) || eval(b),
+            Connective::Biimplicate(a, b) => eval(a) == eval(b),
+            Connective::Xor(a, b) => eval(a) != eval(b),
+            Connective::Nand(a, b) => !(eval(a) && eval(b)),
+            Connective::Nor(a, b) => !(eval(a) || eval(b)),
+            Connective::ReverseImplicate(a, b) => eval(a) || !eval(b),
+            atom => self.worlds[world.0].contains(atom),
+        }
+    }
+
+    /// One line per world with the atoms true in it, then the pairs of the
+    /// accessibility relation, e.g. `w0: p, q` and `R: w0 → w1`.
+    pub fn pretty(&self) -> String {
+        let mut lines: Vec<_> = self
+            .worlds
+            .iter()
+            .enumerate()
+            .map(|(w, atoms)| {
+                let atoms: Vec<_> = atoms.iter().map(Connective::pretty).collect();
+                format!("{}: {}", World(w), atoms.join(", "))
+            })
+            .collect();
+        let access: Vec<_> = self
+            .access
+            .iter()
+            .map(|(from, to)| format!("{} → {}", from, to))
+            .collect();
+        lines.push(format!("R: {}", access.join(", ")));
+        lines.join("\n")
+    }
+
+    pub fn generate_dot(&self) -> String {
+        let nodes = self
+            .worlds
+            .iter()
+            .enumerate()
+            .map(|(w, atoms)| {
+                let atoms: Vec<_> = atoms.iter().map(Connective::pretty).collect();
+                format!(r#"{} [label="{}\n{}"];"#, w, World(w), atoms.join(", "))
+            })
+            .collect::<Vec<_>>()
+            .join("\n\t");
+        let edges = self
+            .access
+            .iter()
+            .map(|(from, to)| format!("{} -> {};", from.0, to.0))
+            .collect::<Vec<_>>()
+            .join("\n\t");
+        format!("digraph M {{\n\t{}\n\n\t{}\t\n}}", nodes, edges)
+    }
+}
+
+/// A labelled tableau for propositional modal logic, where every fact belongs to a
+/// world and `◇φ` true or `□φ` false adds a new world seen by the current one.
+///
+/// Quantified and temporal formulas, predicates and equalities are treated as
+/// atoms. A saturated branch that stays open gives a [`KripkeModel`] in which the
+/// starting facts hold in `w0`. A tableau too big to finish reports
+/// [`Warning::Unfinished`] instead.
+#[derive(Debug, Clone)]
+pub struct ModalTableau {
+    system: ModalSystem,
+    facts_counter: usize,
+    process_counter: usize,
+    nodes: Vec<Node>,
+    edges: Vec<Edge>,
+    countermodels: Vec<KripkeModel>,
+    warnings: Vec<Warning>,
+}
+
+impl ModalTableau {
+    /// A tableau starting with each formula true or false in the world `w0`.
+    pub fn new(system: ModalSystem, start: Vec<(Connective, bool)>) -> ModalTableau {
+        let mut tableau = ModalTableau {
+            system,
+            facts_counter: 0,
+            process_counter: 0,
+            nodes: vec![],
+            edges: vec![],
+            countermodels: vec![],
+            warnings: vec![],
+        };
+        let facts = start
+            .into_iter()
+            .map(|(con, expect)| Fact::Holds(World(0), con, expect))
+            .collect();
+        let branch = Branch {
+            worlds: 1,
+            ..Branch::default()
+        };
+        tableau.add_node(None, branch, facts);
+        tableau
+    }
+    fn add_node(&mut self, from: Option<(NodeId, FactId)>, mut branch: Branch, facts: Vec<Fact>) {
+        let node_id = NodeId(self.nodes.len());
+        let facts: Vec<_> = facts
+            .into_iter()
+            .map(|fact| {
+                let fact_id = FactId(self.facts_counter);
+                self.facts_counter += 1;
+                (fact_id, fact)
+            })
+            .collect();
+        self.nodes.push(Node {
+            facts: facts.clone(),
+            closed: false,
+        });
+        if let Some((origin_node, fact)) = from {
+            self.edges.push(Edge {
+                origin_node,
+                fact,
+                to: node_id,
+            });
+        }
+        for (fact_id, fact) in facts {
+            if branch.add(fact_id, fact).is_err() {
+                self.nodes[node_id.0].closed = true;
+                return;
+            }
+        }
+        self.expand(node_id, branch);
+    }
+    /// Applies the next rule to the branch ending in `from`: α-rules first, then
+    /// universal facts, β-rules and finally new worlds, so that a world is fully
+    /// worked out before it is compared with others for blocking.
+    fn expand(&mut self, from: NodeId, mut branch: Branch) {
+        loop {
+            if self.process_counter >= 1000 || self.facts_counter >= 1000 || branch.worlds >= 30 {
+                if self.warnings.is_empty() {
+                    self.warnings.push(Warning::Unfinished);
+                }
+                return;
+            }
+            self.process_counter += 1;
+
+            let single = branch
+                .queue
+                .iter()
+                .position(|(_, _, con, expect)| alternatives(con, *expect).len() == 1);
+            if let Some(index) = single {
+                let (fact_id, world, con, expect) = branch.queue.remove(index);
+                let facts = alternatives(&con, expect).remove(0);
+                let facts = facts
+                    .into_iter()
+                    .map(|(con, expect)| Fact::Holds(world, con, expect))
+                    .collect();
+                return self.add_node(Some((from, fact_id)), branch, facts);
+            }
+
+            if let Some((index, to, facts)) = branch.next_universal(self.system) {
+                let universal = &mut branch.universals[index];
+                universal.4.insert(to);
+                let fact_id = universal.0;
+                let facts: Vec<_> = facts
+                    .into_iter()
+                    .filter(|fact| match fact {
+                        Fact::Holds(world, con, expect) => !branch.holds(*world, con, *expect),
+                        Fact::Access(_, _) => true,
+                    })
+                    .collect();
+                if facts.is_empty() {
+                    continue;
+                }
+                return self.add_node(Some((from, fact_id)), branch, facts);
+            }
+
+            let split = branch
+                .queue
+                .iter()
+                .position(|(_, _, con, expect)| alternatives(con, *expect).len() == 2);
+            if let Some(index) = split {
+                let (fact_id, world, con, expect) = branch.queue.remove(index);
+                for facts in alternatives(&con, expect) {
+                    let facts = facts
+                        .into_iter()
+                        .map(|(con, expect)| Fact::Holds(world, con, expect))
+                        .collect();
+                    self.add_node(Some((from, fact_id)), branch.clone(), facts);
+                }
+                return;
+            }
+
+            let system = self.system;
+            let witness_needed = branch.queue.iter().position(|(_, world, con, expect)| {
+                let inner = match con {
+                    Connective::Box(inner) | Connective::Diamond(inner) => &**inner,
+                    _ => return false,
+                };
+                let witnessed = branch
+                    .successors(system, *world)
+                    .into_iter()
+                    .any(|to| branch.holds(to, inner, *expect));
+                !witnessed && branch.blocked_by(system, *world).is_none()
+            });
+            if let Some(index) = witness_needed {
+                let (fact_id, world, con, expect) = branch.queue.remove(index);
+                let inner = match con {
+                    Connective::Box(inner) | Connective::Diamond(inner) => *inner,
+                    _ => unreachable!(),
+                };
+                let new_world = World(branch.worlds);
+                branch.worlds += 1;
+                let facts = vec![
+                    Fact::Access(world, new_world),
+                    Fact::Holds(new_world, inner, expect),
+                ];
+                return self.add_node(Some((from, fact_id)), branch, facts);
+            }
+
+            self.countermodels.push(branch.model(self.system));
+            return;
+        }
+    }
+    /// Whether every branch of the tableau ends in a contradiction, making the
+    /// starting facts impossible in every model of the system. An unfinished
+    /// tableau is never closed, see [`ModalTableau::warnings`].
+    pub fn is_closed(&self) -> bool {
+        self.nodes
+            .iter()
+            .enumerate()
+            .all(|(id, node)| node.closed || self.edges.iter().any(|edge| edge.origin_node.0 == id))
+    }
+    /// A model of the starting facts read off the first open branch, if any branch
+    /// was worked out completely without closing.
+    pub fn countermodel(&self) -> Option<&KripkeModel> {
+        self.countermodels.first()
+    }
+    /// [`Warning::Unfinished`] if a branch was left neither closed nor worked out.
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+    /// The tableau in DOT format, where every fact names the world it is about.
+    pub fn generate_dot(&self) -> String {
+        let nodes = self.nodes.iter().map(|node| {
+            let facts = node
+                .facts
+                .iter()
+                .map(|(id, fact)| (*id, fact.pretty()))
+                .collect();
+            (facts, node.closed)
+        });
+        render_dot(nodes, &self.edges)
+    }
+}
+
+#[cfg(test)]
+use crate::parse::{parse, parse_argument};
+
+#[cfg(test)]
+fn valid_in(system: ModalSystem, src: &str) -> bool {
+    let con = parse(src).unwrap();
+    let tableau = ModalTableau::new(system, vec![(con.clone(), false)]);
+    if !tableau.is_closed() {
+        let model = tableau.countermodel().expect("open branch without a model");
+        assert!(
+            !model.evaluate(World(0), &con),
+            "{}\n{}",
+            src,
+            model.pretty()
+        );
+    }
+    tableau.is_closed()
+}
+
+#[test]
+fn modal_axioms() {
+    use self::ModalSystem::*;
+    let axioms = [
+        ("□(p → q) → (□p → □q)", [true, true, true, true]),
+        ("◇p ↔ ¬□¬p", [true, true, true, true]),
+        ("□p → p", [false, true, true, true]),
+        ("p → ◇p", [false, true, true, true]),
+        ("□p → □□p", [false, false, true, true]),
+        ("◇◇p → ◇p", [false, false, true, true]),
+        ("◇p → □◇p", [false, false, false, true]),
+        ("p → □◇p", [false, false, false, true]),
+        ("□p → ◇p", [false, true, true, true]),
+        ("□(p ∨ q) → □p ∨ □q", [false, false, false, false]),
+        ("◇p ∧ ◇q → ◇(p ∧ q)", [false, false, false, false]),
+    ];
+    for (src, expected) in &axioms {
+        for (system, expected) in [K, T, S4, S5].iter().zip(expected) {
+            assert_eq!(valid_in(*system, src), *expected, "{} in {:?}", src, system);
+        }
+    }
+}
+
+#[test]
+fn countermodels() {
+    let tableau = ModalTableau::new(ModalSystem::K, vec![(parse("□p → p").unwrap(), false)]);
+    let model = tableau.countermodel().unwrap();
+    assert_eq!(model.worlds.len(), 1);
+    assert!(model.access.is_empty());
+
+    let argument = parse_argument("◇p, ◇q / ◇(p ∧ q)").unwrap();
+    let tableau = ModalTableau::new(ModalSystem::S5, argument.tableau_start());
+    let model = tableau.countermodel().unwrap();
+    assert_eq!(model.worlds.len(), 3);
+    for premise in &argument.premises {
+        assert!(model.evaluate(World(0), premise));
+    }
+    assert!(!model.evaluate(World(0), &argument.conclusion));
+}
+
+#[test]
+fn dot_labels_facts_with_worlds() {
+    let dot =
+        ModalTableau::new(ModalSystem::T, vec![(parse("□p → p").unwrap(), false)]).generate_dot();
+    assert!(dot.contains("[1] w0: □p → p: false"));
+    assert!(dot.contains("w0: p: false"));
+    assert!(dot.contains("\\nx"));
+
+    let dot = ModalTableau::new(ModalSystem::K, vec![(parse("◇p").unwrap(), true)]).generate_dot();
+    assert!(dot.contains("w0 R w1"));
+    assert!(dot.contains("w1: p: true"));
+}
+
+#[test]
+fn running_out_of_worlds() {
+    let src = format!("{}p", "◇".repeat(40));
+    let tableau = ModalTableau::new(ModalSystem::K, vec![(parse(&src).unwrap(), true)]);
+    assert!(!tableau.is_closed());
+    assert!(tableau.countermodel().is_none());
+    assert_eq!(tableau.warnings(), &[Warning::Unfinished]);
+
+    let tableau = ModalTableau::new(ModalSystem::K, vec![(parse("◇◇p").unwrap(), true)]);
+    assert!(tableau.warnings().is_empty());
+}
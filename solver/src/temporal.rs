@@ -0,0 +1,201 @@
+use crate::ast::Connective;
+use crate::visit::{rebuild_connective, Fold};
+use crate::Table;
+use indexmap::{IndexMap, IndexSet};
+
+/// Replaces each temporal operator with ⊤ or ⊥, whichever it is at `time` of the
+/// trace, leaving a formula to evaluate in the state at that time.
+struct AtTime<'a> {
+    trace: &'a [IndexMap<String, bool>],
+    time: usize,
+}
+
+impl Fold for AtTime<'_> {
+    fn fold_connective(&mut self, con: &Connective) -> Connective {
+        let (trace, time) = (self.trace, self.time);
+        let later = || time..trace.len().max(time);
+        let holds = match con {
+            Connective::Next(x) => time + 1 < trace.len() && x.solve_at(trace, time + 1),
+            Connective::Eventually(x) => later().any(|t| x.solve_at(trace, t)),
+            Connective::Always(x) => later().all(|t| x.solve_at(trace, t)),
+            Connective::Until(a, b) => later()
+                .find(|t| b.solve_at(trace, *t))
+                .map(|until| (time..until).all(|t| a.solve_at(trace, t)))
+                .unwrap_or(false),
+            _ => return rebuild_connective(self, con),
+        };
+        if holds {
+            Connective::Top
+        } else {
+            Connective::Bottom
+        }
+    }
+}
+
+impl Connective {
+    /// Evaluates the formula in the first state of a finite trace, where every state
+    /// assigns the propositions like the assignment given to [`Connective::solve`].
+    ///
+    /// `X φ` needs a next state for `φ` to hold in, so it is false in the last
+    /// state, while `G φ` only looks at the states up to the end of the trace.
+    /// Formulas that aren't propositional are judged like in [`Connective::solve`].
+    pub fn solve_trace(&self, trace: &[IndexMap<String, bool>]) -> bool {
+        self.solve_at(trace, 0)
+    }
+
+    /// Evaluates the formula in the state at `time` of a finite trace, see
+    /// [`Connective::solve_trace`].
+    pub fn solve_at(&self, trace: &[IndexMap<String, bool>], time: usize) -> bool {
+        let now = AtTime { trace, time }.fold_connective(self);
+        match trace.get(time) {
+            Some(state) => now.solve(state),
+            None => now.solve(&IndexMap::new()),
+        }
+    }
+
+    /// A table with a row for every state of the trace, giving the value of each
+    /// proposition and then of each subformula at that time. Headers are made by `f`
+    /// as for [`Connective::generate_table_generic`].
+    pub fn generate_trace_table_generic<F>(
+        &self,
+        trace: &[IndexMap<String, bool>],
+        mut f: F,
+    ) -> Table
+    where
+        F: FnMut(&Connective) -> String,
+    {
+        let mut variables: IndexSet<String> = self.all_variables().into_iter().collect();
+        for state in trace {
+            variables.extend(state.keys().cloned());
+        }
+        let sub_connectives = self.all_sub_connectives(true);
+
+        let headers = variables
+            .iter()
+            .cloned()
+            .chain(sub_connectives.iter().map(|p| f(p)))
+            .collect();
+        let rows = trace
+            .iter()
+            .enumerate()
+            .map(|(time, state)| {
+                variables
+                    .iter()
+                    .map(|x| state.get(x).cloned().unwrap_or(false))
+                    .chain(sub_connectives.iter().map(|x| x.solve_at(trace, time)))
+                    .collect()
+            })
+            .collect();
+
+        Table { headers, rows }
+    }
+
+    pub fn generate_trace_table_latex(&self, trace: &[IndexMap<String, bool>]) -> Table {
+        self.generate_trace_table_generic(trace, |p| p.pretty_latex())
+    }
+
+    pub fn generate_trace_table(&self, trace: &[IndexMap<String, bool>]) -> Table {
+        self.generate_trace_table_generic(trace, |p| p.pretty())
+    }
+}
+
+#[cfg(test)]
+use crate::parse::{parse, ParseOptions};
+
+/// Parses `src` with the temporal operators written as words.
+#[cfg(test)]
+fn ltl(src: &str) -> Connective {
+    let options = ParseOptions {
+        keywords: true,
+        ..ParseOptions::default()
+    };
+    options.parse(src).unwrap()
+}
+
+#[cfg(test)]
+fn trace(states: &[&[(&str, bool)]]) -> Vec<IndexMap<String, bool>> {
+    states
+        .iter()
+        .map(|state| {
+            state
+                .iter()
+                .map(|(name, value)| (name.to_string(), *value))
+                .collect()
+        })
+        .collect()
+}
+
+#[test]
+fn finite_trace_semantics() {
+    let states = trace(&[
+        &[("p", true), ("q", false)],
+        &[("p", true), ("q", false)],
+        &[("p", false), ("q", true)],
+    ]);
+    let holds = |src: &str, time| ltl(src).solve_at(&states, time);
+
+    assert!(holds("p until q", 0));
+    assert!(holds("always (p ∨ q)", 0));
+    assert!(holds("eventually q ∧ ¬q", 0));
+    assert!(holds("next next q", 0));
+    assert!(!holds("next next next q", 0));
+    assert!(!holds("next ⊤", 2));
+    assert!(!holds("always p", 0));
+    assert!(holds("always q", 2));
+    assert!(holds("q until p", 0));
+    assert!(!holds("p until (¬p ∧ ¬q)", 0));
+
+    let one = trace(&[&[("p", true)]]);
+    assert_eq!(
+        ltl("eventually p ∧ always p").solve_trace(&one),
+        ltl("eventually p ∧ always p").solve(&one[0])
+    );
+    assert!(ltl("always ¬p").solve_trace(&[]));
+    assert!(!ltl("eventually p").solve_trace(&[]));
+}
+
+#[test]
+fn temporal_letters_and_printing() {
+    let options = ParseOptions {
+        temporal_letters: true,
+        ..ParseOptions::default()
+    };
+    let con = options.parse("G (p → F q) ∧ p U X q").unwrap();
+    assert_eq!(con, ltl("always (p → eventually q) ∧ (p until next q)"));
+    assert_eq!(con.pretty(), "G (p → F q) ∧ p U X q");
+    assert_eq!(options.parse(&con.pretty()).unwrap(), con);
+    let latex = ParseOptions {
+        latex_commands: true,
+        ..ParseOptions::default()
+    };
+    assert_eq!(latex.parse(&con.pretty_latex()).unwrap(), con);
+    assert_eq!(parse("X ∧ F").unwrap().all_variables().len(), 2);
+}
+
+#[test]
+fn printed_letters_parse_back_by_default() {
+    for src in &[
+        "always (p → eventually q) ∧ (p until next q)",
+        "always eventually (p until (q ∨ next next r))",
+        "∀x always P(x) ∧ eventually ¬(x ≈ c)",
+        "(p until q) until always p",
+    ] {
+        let con = ltl(src);
+        assert_eq!(parse(&con.pretty()).unwrap(), con, "{}", con.pretty());
+    }
+    // Where a name fits the letters stay names.
+    for src in &["G(c) ∧ ∀F P(F)", "∃X:U F(X)", "X ∧ G → U"] {
+        assert_eq!(parse(src).unwrap().pretty(), *src);
+    }
+}
+
+#[test]
+fn trace_table() {
+    let states = trace(&[&[("p", true)], &[("p", false)]]);
+    let table = ltl("p ∧ next ¬p").generate_trace_table(&states);
+    assert_eq!(table.headers, vec!["p", "X ¬p", "p ∧ X ¬p"]);
+    assert_eq!(
+        table.rows,
+        vec![vec![true, true, true], vec![false, false, false]]
+    );
+}
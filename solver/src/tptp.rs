@@ -0,0 +1,614 @@
+use crate::ast::{Argument, Connective, Term};
+use crate::parse::{ParseError, Span};
+use crate::precedence::Operator;
+use crate::problem::Problem;
+use indexmap::IndexMap;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Tok {
+    /// A name starting with a lower case letter or digit, or a quoted name.
+    Word(String),
+    /// A variable, starting with an upper case letter.
+    Upper(String),
+    /// A defined word like `$true`, without the `$`.
+    Defined(String),
+    Symbol(&'static str),
+    Eof,
+}
+
+type Spanned = (Tok, Span);
+
+type TptpResult<T> = Result<T, ParseError>;
+
+/// Symbols of the TPTP syntax, longest first.
+const SYMBOLS: [&str; 20] = [
+    "<=>", "<~>", "=>", "<=", "~|", "~&", "!=", "~", "&", "|", "=", "!", "?", "(", ")", "[", "]",
+    ",", ".", ":",
+];
+
+fn invalid(expected: &'static str, span: Span) -> ParseError {
+    ParseError::InvalidStatement { expected, span }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+fn lex(src: &str) -> TptpResult<Vec<Spanned>> {
+    let mut tokens = vec![];
+    let mut i = 0;
+
+    while let Some(c) = src[i..].chars().next() {
+        let rest = &src[i..];
+        if c.is_whitespace() {
+            i += c.len_utf8();
+        } else if c == '%' {
+            i += rest.find('\n').unwrap_or(rest.len());
+        } else if rest.starts_with("/*") {
+            let end = rest
+                .find("*/")
+                .ok_or_else(|| invalid("`*/` to close the comment", Span::new(i, src.len())))?;
+            i += end + 2;
+        } else if let Some(symbol) = SYMBOLS.iter().find(|symbol| rest.starts_with(*symbol)) {
+            tokens.push((Tok::Symbol(symbol), Span::new(i, i + symbol.len())));
+            i += symbol.len();
+        } else if c == '\'' {
+            let mut name = String::new();
+            let mut chars = rest.char_indices().skip(1);
+            let len = loop {
+                match chars.next() {
+                    Some((j, '\'')) => break j + 1,
+                    Some((_, '\\')) => name.extend(chars.next().map(|(_, c)| c)),
+                    Some((_, c)) => name.push(c),
+                    None => return Err(invalid("`'` to close the name", Span::new(i, src.len()))),
+                }
+            };
+            tokens.push((Tok::Word(name), Span::new(i, i + len)));
+            i += len;
+        } else {
+            let start = if c == '$' { 1 } else { 0 };
+            let len = start
+                + rest[start..]
+                    .find(|c| !is_word_char(c))
+                    .unwrap_or(rest.len() - start);
+            if len == start {
+                return Err(invalid("a TPTP formula", Span::new(i, i + c.len_utf8())));
+            }
+            let word = rest[start..len].to_string();
+            let token = match c {
+                '$' => Tok::Defined(word),
+                c if c.is_ascii_uppercase() => Tok::Upper(word),
+                _ => Tok::Word(word),
+            };
+            tokens.push((token, Span::new(i, i + len)));
+            i += len;
+        }
+    }
+    tokens.push((Tok::Eof, Span::new(src.len(), src.len())));
+
+    Ok(tokens)
+}
+
+fn expect<'t>(
+    tokens: &'t [Spanned],
+    symbol: &'static str,
+    expected: &'static str,
+) -> TptpResult<&'t [Spanned]> {
+    match tokens {
+        [(Tok::Symbol(s), _), rest @ ..] if *s == symbol => Ok(rest),
+        [(_, span), ..] => Err(invalid(expected, *span)),
+        [] => unreachable!("token stream ends with Eof"),
+    }
+}
+
+fn binary_operator(symbol: &str) -> Option<Operator> {
+    match symbol {
+        "&" => Some(Operator::And),
+        "|" => Some(Operator::Or),
+        "=>" => Some(Operator::Implicate),
+        "<=" => Some(Operator::ReverseImplicate),
+        "<=>" => Some(Operator::Biimplicate),
+        "<~>" => Some(Operator::Xor),
+        "~&" => Some(Operator::Nand),
+        "~|" => Some(Operator::Nor),
+        _ => None,
+    }
+}
+
+struct Parser {
+    /// Variables bound by the quantifiers enclosing the current position.
+    bound: Vec<String>,
+    /// The free variables met so far in a clause, which are implicitly universally
+    /// quantified. `None` outside of clauses, where free variables are an error.
+    free: Option<Vec<String>>,
+}
+
+impl Parser {
+    /// Parses a formula, where `&` and `|` may be chained but the other binary
+    /// connectives need parens around nested uses.
+    fn parse_formula<'t>(
+        &mut self,
+        tokens: &'t [Spanned],
+    ) -> TptpResult<(Connective, &'t [Spanned])> {
+        let (mut left, mut rest) = self.parse_unit(tokens)?;
+        match rest {
+            [(Tok::Symbol(op @ "&"), _), ..] | [(Tok::Symbol(op @ "|"), _), ..] => {
+                let op = *op;
+                while let [(Tok::Symbol(symbol), _), nrest @ ..] = rest {
+                    if *symbol != op {
+                        break;
+                    }
+                    let (right, nrest) = self.parse_unit(nrest)?;
+                    left = binary_operator(op).unwrap().apply(left, right);
+                    rest = nrest;
+                }
+                Ok((left, rest))
+            }
+            [(Tok::Symbol(symbol), _), nrest @ ..] => match binary_operator(symbol) {
+                Some(op) => {
+                    let (right, nrest) = self.parse_unit(nrest)?;
+                    Ok((op.apply(left, right), nrest))
+                }
+                None => Ok((left, rest)),
+            },
+            _ => Ok((left, rest)),
+        }
+    }
+
+    fn parse_unit<'t>(&mut self, tokens: &'t [Spanned]) -> TptpResult<(Connective, &'t [Spanned])> {
+        match tokens {
+            [(Tok::Symbol("~"), _), rest @ ..] => {
+                let (con, rest) = self.parse_unit(rest)?;
+                Ok((Connective::Not(box con), rest))
+            }
+            [(Tok::Symbol(q @ "!"), _), rest @ ..] | [(Tok::Symbol(q @ "?"), _), rest @ ..] => {
+                let mut rest = expect(rest, "[", "`[`")?;
+                let mut names = vec![];
+                loop {
+                    match rest {
+                        [(Tok::Upper(name), _), (Tok::Symbol(","), _), nrest @ ..] => {
+                            names.push(name.clone());
+                            rest = nrest;
+                        }
+                        [(Tok::Upper(name), _), (Tok::Symbol("]"), _), nrest @ ..] => {
+                            names.push(name.clone());
+                            rest = nrest;
+                            break;
+                        }
+                        [(_, span), ..] => return Err(invalid("a variable", *span)),
+                        [] => unreachable!("token stream ends with Eof"),
+                    }
+                }
+                let rest = expect(rest, ":", "`:`")?;
+
+                let depth = self.bound.len();
+                self.bound.extend(names.iter().cloned());
+                let result = self.parse_unit(rest);
+                self.bound.truncate(depth);
+                let (body, rest) = result?;
+
+                let con = names.into_iter().rev().fold(body, |con, name| {
+                    if *q == "!" {
+                        Connective::ForAll(name, None, box con)
+                    } else {
+                        Connective::Exists(name, None, box con)
+                    }
+                });
+                Ok((con, rest))
+            }
+            [(Tok::Symbol("("), _), rest @ ..] => {
+                let (con, rest) = self.parse_formula(rest)?;
+                Ok((con, expect(rest, ")", "`)`")?))
+            }
+            [(Tok::Defined(word), _), rest @ ..] if word == "true" => Ok((Connective::Top, rest)),
+            [(Tok::Defined(word), _), rest @ ..] if word == "false" => {
+                Ok((Connective::Bottom, rest))
+            }
+            _ => self.parse_atom(tokens),
+        }
+    }
+
+    fn parse_atom<'t>(&mut self, tokens: &'t [Spanned]) -> TptpResult<(Connective, &'t [Spanned])> {
+        let (term, rest) = self.parse_term(tokens)?;
+        match rest {
+            [(Tok::Symbol("="), _), rest @ ..] => {
+                let (right, rest) = self.parse_term(rest)?;
+                Ok((Connective::Eq(term, right), rest))
+            }
+            [(Tok::Symbol("!="), _), rest @ ..] => {
+                let (right, rest) = self.parse_term(rest)?;
+                Ok((Connective::Not(box Connective::Eq(term, right)), rest))
+            }
+            _ => match term {
+                Term::Function(p, args) => Ok((Connective::Predicate(p, args), rest)),
+                Term::Const(p) => Ok((Connective::Var(p), rest)),
+                Term::Var(_) => Err(invalid("a formula", tokens[0].1)),
+            },
+        }
+    }
+
+    fn parse_term<'t>(&mut self, tokens: &'t [Spanned]) -> TptpResult<(Term, &'t [Spanned])> {
+        match tokens {
+            [(Tok::Word(f), _), (Tok::Symbol("("), _), rest @ ..] => {
+                let mut args = vec![];
+                let mut rest = rest;
+                loop {
+                    let (arg, nrest) = self.parse_term(rest)?;
+                    args.push(arg);
+                    match nrest {
+                        [(Tok::Symbol(","), _), nrest @ ..] => rest = nrest,
+                        [(Tok::Symbol(")"), _), nrest @ ..] => {
+                            return Ok((Term::Function(f.clone(), args), nrest))
+                        }
+                        [(_, span), ..] => return Err(invalid("`,` or `)`", *span)),
+                        [] => unreachable!("token stream ends with Eof"),
+                    }
+                }
+            }
+            [(Tok::Word(c), _), rest @ ..] => Ok((Term::Const(c.clone()), rest)),
+            [(Tok::Upper(x), span), rest @ ..] => {
+                if !self.bound.contains(x) {
+                    match &mut self.free {
+                        Some(free) if free.contains(x) => {}
+                        Some(free) => free.push(x.clone()),
+                        None => return Err(invalid("a bound variable", *span)),
+                    }
+                }
+                Ok((Term::Var(x.clone()), rest))
+            }
+            [(_, span), ..] => Err(invalid("a term", *span)),
+            [] => unreachable!("token stream ends with Eof"),
+        }
+    }
+}
+
+/// Skips the optional source and useful info after the formula of an annotated
+/// formula, up to its closing paren.
+fn skip_annotations(tokens: &[Spanned]) -> &[Spanned] {
+    if let [(Tok::Symbol(","), _), ..] = tokens {
+        let mut depth = 0;
+        for (i, (token, _)) in tokens.iter().enumerate() {
+            match token {
+                Tok::Symbol("(") | Tok::Symbol("[") => depth += 1,
+                Tok::Symbol(")") | Tok::Symbol("]") if depth == 0 => return &tokens[i..],
+                Tok::Symbol(")") | Tok::Symbol("]") => depth -= 1,
+                Tok::Eof => return &tokens[i..],
+                _ => {}
+            }
+        }
+    }
+    tokens
+}
+
+/// Reads the `fof` and `cnf` annotated formulas of a TPTP problem.
+///
+/// Formulas with the role `conjecture` become goals and all others become
+/// premises. The variables of a clause are universally quantified in the order
+/// they first appear. A `negated_conjecture` is a premise as well, and when there
+/// is no conjecture the goal is ⊥, so the problem is valid exactly when the
+/// formulas are unsatisfiable.
+pub fn parse_tptp(src: &str) -> Result<Problem, ParseError> {
+    let tokens = lex(src)?;
+    let mut problem = Problem::default();
+    let mut negated_conjecture = false;
+    let mut rest = &tokens[..];
+
+    loop {
+        let (clause, nrest) = match rest {
+            [(Tok::Eof, _)] => break,
+            [(Tok::Word(kind), _), nrest @ ..] if kind == "fof" || kind == "cnf" => {
+                (kind == "cnf", nrest)
+            }
+            [(_, span), ..] => return Err(invalid("`fof` or `cnf`", *span)),
+            [] => unreachable!("token stream ends with Eof"),
+        };
+        let nrest = expect(nrest, "(", "`(`")?;
+        let (name, nrest) = match nrest {
+            [(Tok::Word(name), _), (Tok::Symbol(","), _), nrest @ ..] => (name.clone(), nrest),
+            [(_, span), ..] => return Err(invalid("a formula name", *span)),
+            [] => unreachable!("token stream ends with Eof"),
+        };
+        let (role, role_span, nrest) = match nrest {
+            [(Tok::Word(role), span), (Tok::Symbol(","), _), nrest @ ..] => (role, *span, nrest),
+            [(_, span), ..] => return Err(invalid("a formula role", *span)),
+            [] => unreachable!("token stream ends with Eof"),
+        };
+
+        let mut parser = Parser {
+            bound: vec![],
+            free: if clause { Some(vec![]) } else { None },
+        };
+        let (con, nrest) = parser.parse_formula(nrest)?;
+        let con = parser
+            .free
+            .unwrap_or_default()
+            .into_iter()
+            .rev()
+            .fold(con, |con, x| Connective::ForAll(x, None, box con));
+
+        let nrest = expect(skip_annotations(nrest), ")", "`)`")?;
+        rest = expect(nrest, ".", "`.`")?;
+
+        match role.as_str() {
+            "conjecture" => problem.goals.push((Some(name), con)),
+            "negated_conjecture" => {
+                negated_conjecture = true;
+                problem.premises.push((Some(name), con));
+            }
+            "axiom" | "hypothesis" | "definition" | "assumption" | "lemma" | "theorem"
+            | "corollary" | "plain" => problem.premises.push((Some(name), con)),
+            _ => return Err(invalid("a formula role", role_span)),
+        }
+    }
+
+    if negated_conjecture && problem.goals.is_empty() {
+        problem.goals.push((None, Connective::Bottom));
+    }
+    Ok(problem)
+}
+
+/// A name as a TPTP word, quoted unless it is a lower case word.
+fn tptp_name(name: &str) -> String {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if (c.is_ascii_lowercase() || c.is_ascii_digit()) && chars.all(is_word_char) => {
+            name.to_string()
+        }
+        _ => format!("'{}'", name.replace('\\', "\\\\").replace('\'', "\\'")),
+    }
+}
+
+/// A TPTP variable for the bound variable `name`, which has to start with an upper
+/// case letter and must differ from the variables given to other names.
+fn tptp_variable(name: &str, variables: &IndexMap<String, String>) -> String {
+    let word: String = name.chars().filter(|&c| is_word_char(c)).collect();
+    let base = match word.chars().next() {
+        Some(c) if c.is_ascii_alphabetic() => c.to_ascii_uppercase().to_string() + &word[1..],
+        _ => format!("X{}", word),
+    };
+    let mut candidate = base.clone();
+    let mut n = 1;
+    while variables.values().any(|taken| *taken == candidate) {
+        candidate = format!("{}{}", base, n);
+        n += 1;
+    }
+    candidate
+}
+
+impl Term {
+    fn tptp_helper(&self, variables: &IndexMap<String, String>) -> String {
+        match self {
+            Term::Var(x) => variables
+                .get(x)
+                .cloned()
+                .unwrap_or_else(|| tptp_variable(x, variables)),
+            Term::Const(c) => tptp_name(c),
+            Term::Function(f, args) => format!(
+                "{}({})",
+                tptp_name(f),
+                args.iter()
+                    .map(|arg| arg.tptp_helper(variables))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        }
+    }
+}
+
+impl Connective {
+    /// The formula in TPTP syntax, e.g. `! [X] : (man(X) => mortal(X))`.
+    ///
+    /// Bound variables are renamed to start with an upper case letter as TPTP
+    /// requires, and other names are quoted unless they are lower case words. `fof`
+    /// is untyped, so sort annotations are left out, and has no modal or temporal
+    /// operators, so formulas like `□φ` are written as propositions named after them.
+    pub fn to_tptp(&self) -> String {
+        self.tptp_helper(&mut IndexMap::new())
+    }
+
+    fn tptp_helper(&self, variables: &mut IndexMap<String, String>) -> String {
+        let symbol = match self {
+            Connective::Var(p) => return tptp_name(p),
+            Connective::Top => return "$true".to_string(),
+            Connective::Bottom => return "$false".to_string(),
+            Connective::Error => return "'?'".to_string(),
+            Connective::Box(_)
+            | Connective::Diamond(_)
+            | Connective::Next(_)
+            | Connective::Eventually(_)
+            | Connective::Always(_)
+            | Connective::Until(_, _) => return tptp_name(&self.pretty()),
+            Connective::Predicate(p, args) => {
+                return Term::Function(p.clone(), args.clone()).tptp_helper(variables)
+            }
+            Connective::Eq(a, b) => {
+                return format!(
+                    "{} = {}",
+                    a.tptp_helper(variables),
+                    b.tptp_helper(variables)
+                )
+            }
+            Connective::Not(x) => return format!("~ {}", x.tptp_operand(variables)),
+            Connective::ForAll(_, _, _) | Connective::Exists(_, _, _) => {
+                let (names, body) = self.quantifier_block(true);
+                let names: Vec<_> = names
+                    .into_iter()
+                    .map(|(name, _)| {
+                        if !variables.contains_key(name) {
+                            let variable = tptp_variable(name, variables);
+                            variables.insert(name.to_string(), variable);
+                        }
+                        variables[name].clone()
+                    })
+                    .collect();
+                let q = if let Connective::ForAll(_, _, _) = self {
+                    "!"
+                } else {
+                    "?"
+                };
+                return format!(
+                    "{} [{}] : {}",
+                    q,
+                    names.join(", "),
+                    body.tptp_operand(variables)
+                );
+            }
+            Connective::And(_, _) => "&",
+            Connective::Or(_, _) => "|",
+            Connective::Implicate(_, _) => "=>",
+            Connective::Biimplicate(_, _) => "<=>",
+            Connective::Xor(_, _) => "<~>",
+            Connective::Nand(_, _) => "~&",
+            Connective::Nor(_, _) => "~|",
+            Connective::ReverseImplicate(_, _) => "<=",
+        };
+        match self {
+            Connective::And(a, b)
+            | Connective::Or(a, b)
+            | Connective::Implicate(a, b)
+            | Connective::Biimplicate(a, b)
+            | Connective::Xor(a, b)
+            | Connective::Nand(a, b)
+            | Connective::Nor(a, b)
+            | Connective::ReverseImplicate(a, b) => format!(
+                "{} {} {}",
+                a.tptp_operand(variables),
+                symbol,
+                b.tptp_operand(variables)
+            ),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Like `tptp_helper`, but with parens around binary formulas.
+    fn tptp_operand(&self, variables: &mut IndexMap<String, String>) -> String {
+        match Operator::of(self) {
+            None | Some(Operator::Not) | Some(Operator::Quantifier) => self.tptp_helper(variables),
+            Some(_) => format!("({})", self.tptp_helper(variables)),
+        }
+    }
+}
+
+impl Problem {
+    /// The problem as TPTP `fof` formulas, with the premises as axioms and the goals
+    /// as conjectures. Premises and goals without a name are numbered.
+    pub fn to_tptp(&self) -> String {
+        let mut out = String::new();
+        if let Some(name) = &self.name {
+            out += &format!("% {}\n", name);
+        }
+        let formulas = self
+            .premises
+            .iter()
+            .enumerate()
+            .map(|(i, premise)| ("axiom", "premise", i, premise))
+            .chain(
+                self.goals
+                    .iter()
+                    .enumerate()
+                    .map(|(i, goal)| ("conjecture", "goal", i, goal)),
+            );
+        for (role, prefix, i, (name, con)) in formulas {
+            let name = match name {
+                Some(name) => tptp_name(name),
+                None => format!("{}_{}", prefix, i + 1),
+            };
+            out += &format!("fof({}, {}, {}).\n", name, role, con.to_tptp());
+        }
+        out
+    }
+}
+
+impl Argument {
+    /// The argument as TPTP `fof` formulas, see [`Problem::to_tptp`].
+    pub fn to_tptp(&self) -> String {
+        Problem {
+            name: None,
+            premises: self
+                .premises
+                .iter()
+                .cloned()
+                .map(|premise| (None, premise))
+                .collect(),
+            goals: vec![(None, self.conclusion.clone())],
+        }
+        .to_tptp()
+    }
+}
+
+#[cfg(test)]
+use crate::parse::{parse, parse_argument};
+
+#[cfg(test)]
+const SAMPLES: [&str; 3] = [
+    include_str!("../samples/tptp/propositional.p"),
+    include_str!("../samples/tptp/syllogism.p"),
+    include_str!("../samples/tptp/clauses.p"),
+];
+
+#[test]
+fn samples_round_trip() {
+    for src in SAMPLES.iter() {
+        let problem = parse_tptp(src).unwrap();
+        let tptp = problem.to_tptp();
+        let reparsed = parse_tptp(&tptp).unwrap_or_else(|err| panic!("{}", err.render(&tptp)));
+        assert_eq!(reparsed.arguments(), problem.arguments());
+        assert_eq!(parse_tptp(&reparsed.to_tptp()).unwrap(), reparsed);
+    }
+}
+
+#[test]
+fn samples_are_valid() {
+    for src in SAMPLES.iter() {
+        for argument in parse_tptp(src).unwrap().arguments() {
+            assert!(argument.tableau().is_closed(), "{}", argument.pretty());
+        }
+    }
+}
+
+#[test]
+fn reading_tptp() {
+    let problem = parse_tptp(SAMPLES[1]).unwrap();
+    assert_eq!(problem.premises.len(), 4);
+    assert_eq!(problem.premises[3].0, Some("Every teacher".to_string()));
+    assert_eq!(
+        problem.premises[0].1,
+        parse("∀X (man(X) → mortal(X))").unwrap()
+    );
+    assert_eq!(
+        problem.goals,
+        vec![(
+            Some("mortal_teacher".to_string()),
+            parse("∃X mortal(teacher(X))").unwrap()
+        )]
+    );
+
+    let problem = parse_tptp(SAMPLES[2]).unwrap();
+    assert_eq!(
+        problem.premises[1].1,
+        parse("∀X,Y,Z. ¬ancestor(X, Y) ∨ ¬ancestor(Y, Z) ∨ ancestor(X, Z)").unwrap()
+    );
+    assert_eq!(problem.goals, vec![(None, Connective::Bottom)]);
+
+    assert!(parse_tptp("fof(a, axiom, p(X)).").is_err());
+    assert!(parse_tptp("fof(a, question, p).").is_err());
+    assert!(parse_tptp("fof(a, axiom, p)").is_err());
+}
+
+#[test]
+fn writing_tptp() {
+    assert_eq!(
+        parse("∀x ∀y (P(x) ∧ x ≈ y → ¬Q(f(y), john))")
+            .unwrap()
+            .to_tptp(),
+        "! [X, Y] : (('P'(X) & X = Y) => ~ 'Q'(f(Y), john))"
+    );
+    assert_eq!(
+        parse("∀x ∃X P(x, X)").unwrap().to_tptp(),
+        "! [X] : ? [X1] : 'P'(X, X1)"
+    );
+    assert_eq!(
+        parse_argument("p > q, p / q").unwrap().to_tptp(),
+        "fof(premise_1, axiom, p => q).\nfof(premise_2, axiom, p).\nfof(goal_1, conjecture, q).\n"
+    );
+}
@@ -0,0 +1,299 @@
+//! Traversals of [`Connective`] that only spell out the cases they care about.
+//!
+//! Each trait method defaults to the matching `walk_*` or `rebuild_*` function,
+//! which carries on into the operands and terms of a connective. An implementation
+//! overrides the method, handles the connectives it is interested in and calls the
+//! default for the rest:
+//!
+//! ```
+//! # use solver::{parse, Connective};
+//! # use solver::visit::{walk_connective, Visitor};
+//! struct Negations(usize);
+//!
+//! impl Visitor for Negations {
+//!     fn visit_connective(&mut self, con: &Connective) {
+//!         if let Connective::Not(_) = con {
+//!             self.0 += 1;
+//!         }
+//!         walk_connective(self, con);
+//!     }
+//! }
+//!
+//! let mut negations = Negations(0);
+//! negations.visit_connective(&parse("¬p ∧ ¬¬q").unwrap());
+//! assert_eq!(negations.0, 3);
+//! ```
+//!
+//! Quantifiers are walked like any other connective, so an implementation that
+//! needs to know which names are bound matches on `ForAll` and `Exists` itself.
+
+use crate::ast::{Connective, Term};
+
+/// Looks at every subformula and term of a connective, outermost first.
+pub trait Visitor {
+    fn visit_connective(&mut self, con: &Connective) {
+        walk_connective(self, con)
+    }
+
+    fn visit_term(&mut self, term: &Term) {
+        walk_term(self, term)
+    }
+}
+
+/// Visits the terms and then the operands of `con`, left to right.
+pub fn walk_connective<V: Visitor + ?Sized>(visitor: &mut V, con: &Connective) {
+    match con {
+        Connective::Predicate(_, args) => {
+            for arg in args {
+                visitor.visit_term(arg);
+            }
+        }
+        Connective::Eq(a, b) => {
+            visitor.visit_term(a);
+            visitor.visit_term(b);
+        }
+        _ => {
+            for operand in con.operands() {
+                visitor.visit_connective(operand);
+            }
+        }
+    }
+}
+
+/// Visits the arguments of a function term.
+pub fn walk_term<V: Visitor + ?Sized>(visitor: &mut V, term: &Term) {
+    if let Term::Function(_, args) = term {
+        for arg in args {
+            visitor.visit_term(arg);
+        }
+    }
+}
+
+/// Changes a connective in place, outermost first.
+pub trait MutVisitor {
+    fn visit_connective_mut(&mut self, con: &mut Connective) {
+        walk_connective_mut(self, con)
+    }
+
+    fn visit_term_mut(&mut self, term: &mut Term) {
+        walk_term_mut(self, term)
+    }
+}
+
+/// Visits the terms and then the operands of `con`, left to right.
+pub fn walk_connective_mut<V: MutVisitor + ?Sized>(visitor: &mut V, con: &mut Connective) {
+    match con {
+        Connective::Predicate(_, args) => {
+            for arg in args {
+                visitor.visit_term_mut(arg);
+            }
+        }
+        Connective::Eq(a, b) => {
+            visitor.visit_term_mut(a);
+            visitor.visit_term_mut(b);
+        }
+        _ => {
+            for operand in con.operands_mut() {
+                visitor.visit_connective_mut(operand);
+            }
+        }
+    }
+}
+
+/// Visits the arguments of a function term.
+pub fn walk_term_mut<V: MutVisitor + ?Sized>(visitor: &mut V, term: &mut Term) {
+    if let Term::Function(_, args) = term {
+        for arg in args {
+            visitor.visit_term_mut(arg);
+        }
+    }
+}
+
+/// Builds a new connective from an existing one, leaving the original alone.
+pub trait Fold {
+    fn fold_connective(&mut self, con: &Connective) -> Connective {
+        rebuild_connective(self, con)
+    }
+
+    fn fold_term(&mut self, term: &Term) -> Term {
+        rebuild_term(self, term)
+    }
+}
+
+/// The same connective as `con`, with its operands and terms folded.
+pub fn rebuild_connective<F: Fold + ?Sized>(folder: &mut F, con: &Connective) -> Connective {
+    let mut fold = |x: &Connective| box folder.fold_connective(x);
+    match con {
+        Connective::Var(_) | Connective::Top | Connective::Bottom | Connective::Error => {
+            con.clone()
+        }
+        Connective::Predicate(p, args) => Connective::Predicate(
+            p.clone(),
+            args.iter().map(|arg| folder.fold_term(arg)).collect(),
+        ),
+        Connective::Eq(a, b) => Connective::Eq(folder.fold_term(a), folder.fold_term(b)),
+        Connective::Not(x) => Connective::Not(fold(x)),
+        Connective::Box(x) => Connective::Box(fold(x)),
+        Connective::Diamond(x) => Connective::Diamond(fold(x)),
+        Connective::Next(x) => Connective::Next(fold(x)),
+        Connective::Eventually(x) => Connective::Eventually(fold(x)),
+        Connective::Always(x) => Connective::Always(fold(x)),
+        Connective::Until(a, b) => Connective::Until(fold(a), fold(b)),
+        Connective::And(a, b) => Connective::And(fold(a), fold(b)),
+        Connective::Or(a, b) => Connective::Or(fold(a), fold(b)),
+        Connective::Implicate(a, b) => Connective::Implicate(fold(a), fold(b)),
+        Connective::Biimplicate(a, b) => Connective::Biimplicate(fold(a), fold(b)),
+        Connective::Xor(a, b) => Connective::Xor(fold(a), fold(b)),
+        Connective::Nand(a, b) => Connective::Nand(fold(a), fold(b)),
+        Connective::Nor(a, b) => Connective::Nor(fold(a), fold(b)),
+        Connective::ReverseImplicate(a, b) => Connective::ReverseImplicate(fold(a), fold(b)),
+        Connective::ForAll(x, sort, inner) => {
+            Connective::ForAll(x.clone(), sort.clone(), fold(inner))
+        }
+        Connective::Exists(x, sort, inner) => {
+            Connective::Exists(x.clone(), sort.clone(), fold(inner))
+        }
+    }
+}
+
+/// The same term as `term`, with the arguments of a function folded.
+pub fn rebuild_term<F: Fold + ?Sized>(folder: &mut F, term: &Term) -> Term {
+    match term {
+        Term::Var(_) | Term::Const(_) => term.clone(),
+        Term::Function(f, args) => Term::Function(
+            f.clone(),
+            args.iter().map(|arg| folder.fold_term(arg)).collect(),
+        ),
+    }
+}
+
+impl Connective {
+    /// The immediate subformulas, left to right. Atoms have none.
+    pub fn operands(&self) -> Vec<&Connective> {
+        match self {
+            Connective::Var(_)
+            | Connective::Top
+            | Connective::Bottom
+            | Connective::Error
+            | Connective::Predicate(_, _)
+            | Connective::Eq(_, _) => vec![],
+            Connective::Not(x)
+            | Connective::Box(x)
+            | Connective::Diamond(x)
+            | Connective::Next(x)
+            | Connective::Eventually(x)
+            | Connective::Always(x)
+            | Connective::ForAll(_, _, x)
+            | Connective::Exists(_, _, x) => vec![x],
+            Connective::And(a, b)
+            | Connective::Or(a, b)
+            | Connective::Implicate(a, b)
+            | Connective::Biimplicate(a, b)
+            | Connective::Xor(a, b)
+            | Connective::Nand(a, b)
+            | Connective::Nor(a, b)
+            | Connective::ReverseImplicate(a, b)
+            | Connective::Until(a, b) => vec![a, b],
+        }
+    }
+
+    /// The immediate subformulas, left to right, to be changed in place.
+    pub fn operands_mut(&mut self) -> Vec<&mut Connective> {
+        match self {
+            Connective::Var(_)
+            | Connective::Top
+            | Connective::Bottom
+            | Connective::Error
+            | Connective::Predicate(_, _)
+            | Connective::Eq(_, _) => vec![],
+            Connective::Not(x)
+            | Connective::Box(x)
+            | Connective::Diamond(x)
+            | Connective::Next(x)
+            | Connective::Eventually(x)
+            | Connective::Always(x)
+            | Connective::ForAll(_, _, x)
+            | Connective::Exists(_, _, x) => vec![x],
+            Connective::And(a, b)
+            | Connective::Or(a, b)
+            | Connective::Implicate(a, b)
+            | Connective::Biimplicate(a, b)
+            | Connective::Xor(a, b)
+            | Connective::Nand(a, b)
+            | Connective::Nor(a, b)
+            | Connective::ReverseImplicate(a, b)
+            | Connective::Until(a, b) => vec![a, b],
+        }
+    }
+}
+
+#[cfg(test)]
+use crate::parse::parse;
+
+#[test]
+fn mut_visitor_renames_in_place() {
+    struct Rename;
+
+    impl MutVisitor for Rename {
+        fn visit_connective_mut(&mut self, con: &mut Connective) {
+            match con {
+                Connective::Var(x) | Connective::Predicate(x, _) => x.make_ascii_uppercase(),
+                _ => {}
+            }
+            walk_connective_mut(self, con);
+        }
+
+        fn visit_term_mut(&mut self, term: &mut Term) {
+            if let Term::Const(c) = term {
+                *term = Term::Function("f".to_string(), vec![Term::Const(c.clone())]);
+            } else {
+                walk_term_mut(self, term);
+            }
+        }
+    }
+
+    let mut con = parse("∀x (p(x, a) → ¬□q) ∨ g(b) ≈ c").unwrap();
+    Rename.visit_connective_mut(&mut con);
+    assert_eq!(
+        con,
+        parse("∀x (P(x, f(a)) → ¬□Q) ∨ g(f(b)) ≈ f(c)").unwrap()
+    );
+}
+
+#[test]
+fn fold_rewrites_a_copy() {
+    /// Writes `a → b` as `¬a ∨ b`, everywhere.
+    struct Material;
+
+    impl Fold for Material {
+        fn fold_connective(&mut self, con: &Connective) -> Connective {
+            match rebuild_connective(self, con) {
+                Connective::Implicate(a, b) => Connective::Or(box Connective::Not(a), b),
+                folded => folded,
+            }
+        }
+    }
+
+    let con = parse("(p → q) → □(r → p)").unwrap();
+    assert_eq!(
+        Material.fold_connective(&con),
+        parse("¬(¬p ∨ q) ∨ □(¬r ∨ p)").unwrap()
+    );
+    assert_eq!(con, parse("(p → q) → □(r → p)").unwrap());
+
+    struct Terms(Vec<Term>);
+
+    impl Visitor for Terms {
+        fn visit_term(&mut self, term: &Term) {
+            self.0.push(term.clone());
+            walk_term(self, term);
+        }
+    }
+
+    let mut terms = Terms(vec![]);
+    terms.visit_connective(&parse("P(f(a)) ∧ ∃x (x ≈ b)").unwrap());
+    assert_eq!(
+        terms.0.iter().map(Term::pretty).collect::<Vec<_>>(),
+        vec!["f(a)", "a", "x", "b"]
+    );
+}
@@ -1,7 +1,26 @@
 fn main() {
-    let a = solver::parse(r#"(p & q) > r"#).unwrap();
-    let b = solver::parse(r#"!(p > r)"#).unwrap();
-    let c = solver::parse(r#"q > r"#).unwrap();
-    let solved = solver::tableau::Tableau::new(vec![(a, true), (b, true), (c, false)]);
-    println!("{}", solved.generate_dot());
+    let src = match std::env::args().nth(1) {
+        Some(path) => std::fs::read_to_string(&path).unwrap_or_else(|err| {
+            eprintln!("could not read {}: {}", path, err);
+            std::process::exit(1);
+        }),
+        None => include_str!("../problems.txt").to_string(),
+    };
+    let problems = match solver::parse_problems(&src) {
+        Ok(problems) => problems,
+        Err(err) => {
+            eprintln!("{}", err.render(&src));
+            std::process::exit(1);
+        }
+    };
+    for problem in problems {
+        for argument in problem.arguments() {
+            let tableau = argument.tableau();
+            for warning in tableau.warnings() {
+                eprintln!("warning: {}", warning.message());
+            }
+            println!("// {}", argument.pretty());
+            println!("{}", tableau.generate_dot());
+        }
+    }
 }
@@ -30,10 +30,11 @@ enum Msg {
 
 #[derive(Debug)]
 struct LogicState {
+    source: String,
     input: solver::Connective,
     dot_src: String,
     // latex_src: String,
-    error: Option<solver::ParseError>,
+    errors: Vec<solver::ParseError>,
     show_sub_steps: bool,
     expect: bool,
 }
@@ -49,23 +50,22 @@ impl LogicState {
         let new_dot = parsed.clone().tableau_dot_graph(expect);
 
         LogicState {
+            source: start.to_string(),
             input: parsed,
             dot_src: new_dot,
-            error: None,
+            errors: vec![],
             show_sub_steps: false,
             expect: expect,
         }
     }
     fn redo_with(&mut self, value: &str) {
-        match solver::parse(value) {
-            Ok(parsed) => {
-                self.input = parsed;
-                self.redo();
-
-                self.error = None;
-            }
-            Err(err) => self.error = Some(err),
+        self.source = value.to_string();
+        let (parsed, errors) = solver::parse_recovering(value);
+        if errors.is_empty() {
+            self.input = parsed;
+            self.redo();
         }
+        self.errors = errors;
     }
     fn redo(&mut self) {
         let new_dot = self.input.clone().tableau_dot_graph(self.expect);
@@ -354,6 +354,14 @@ impl Renderable<(), Model> for Model {
 
                 let expect_str = format!("Expecting {}", if expect { "true" } else { "false" });
 
+                let error_html = html! {
+                    <div>
+                        {for logic.errors.iter().map(|err| html! {
+                            <pre class="error",>{err.render(&logic.source)}</pre>
+                        })}
+                    </div>
+                };
+
                 html! {
                     <div>
                         <textarea oninput=|e| Msg::Change(e), />
@@ -361,6 +369,7 @@ impl Renderable<(), Model> for Model {
                             Msg::ShowSubSteps(e.value)
                         },/>
                         <button onclick=|_| Msg::Expect(!expect), >{expect_str}</button>
+                        {error_html}
                         <details>
                             <summary>{"Table"}</summary>
                             <table>